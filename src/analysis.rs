@@ -0,0 +1,156 @@
+use std::collections::BTreeSet;
+
+use crate::tokenizer::{Span, Spanned, Token, TokenKind};
+
+/// Which kinds of token appear in `tokens`, e.g. to check whether a file uses classes at all
+pub fn token_kinds_used(tokens: &[Token]) -> BTreeSet<TokenKind> {
+	tokens.iter().map(|token| token.kind()).collect()
+}
+
+/// How many branching keywords (`if`, `while`, `for`, `and`, `or`) appear in `tokens`, as a rough measure of
+/// cyclomatic complexity.  Pass the tokens of a single function to score that function.
+pub fn complexity_hint(tokens: &[Token]) -> usize {
+	tokens.iter()
+		.filter(|token| matches!(token, Token::If | Token::While | Token::For | Token::And | Token::Or))
+		.count()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LexWarning {
+	/// Two string literals with nothing but trivia between them, e.g. `"a" "b"`, most likely missing a `+`.
+	/// Holds where both strings are in the source.
+	AdjacentStrings {
+		first: Span,
+		second: Span
+	}
+}
+
+impl LexWarning {
+	pub fn message(&self) -> String {
+		match self {
+			LexWarning::AdjacentStrings { .. } => String::from("Adjacent string literals, did you forget a '+'?")
+		}
+	}
+
+	/// Where the warning points, which for adjacent strings is the second one
+	pub fn span(&self) -> Span {
+		match self {
+			LexWarning::AdjacentStrings { second, .. } => *second
+		}
+	}
+}
+
+impl std::fmt::Display for LexWarning {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Warning: {}", self.message())
+	}
+}
+
+/// Reports every pair of adjacent string literals in `tokens`
+pub fn lint_adjacent_strings(tokens: &[Spanned<Token>]) -> Vec<LexWarning> {
+	let mut warnings = Vec::new();
+	let mut previous_string = None;
+
+	for token in tokens {
+		if token.value.is_trivia() {
+			continue;
+		}
+		if let Token::String(_) = token.value {
+			if let Some(first) = previous_string {
+				warnings.push(LexWarning::AdjacentStrings {
+					first,
+					second: token.span
+				});
+			}
+			previous_string = Some(token.span);
+		} else {
+			previous_string = None;
+		}
+	}
+
+	warnings
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tokenizer::{Tokenizer, TokenizerOptions};
+
+	#[test]
+	fn test_token_kinds_used() {
+		let source = r#"
+		class Greeter {}
+		var name = "World";
+		print "Hello, " + name;
+		print name;
+		"#;
+		let tokens = Tokenizer::new(source).tokenize().unwrap();
+		assert_eq!(token_kinds_used(&tokens), BTreeSet::from([
+			TokenKind::LeftBrace,
+			TokenKind::RightBrace,
+			TokenKind::Plus,
+			TokenKind::Semicolon,
+			TokenKind::Equal,
+			TokenKind::Identifier,
+			TokenKind::String,
+			TokenKind::Class,
+			TokenKind::Print,
+			TokenKind::Var,
+			TokenKind::Eof
+		]));
+		assert!(!token_kinds_used(&tokens).contains(&TokenKind::Fun));
+	}
+
+	#[test]
+	fn test_complexity_hint() {
+		let source = r#"
+		fun classify(n) {
+			if (n < 0 or n > 100) return "out of range";
+			for (var i = 0; i < n; i = i + 1) {
+				while (i > 10 and i < 20) i = i + 1;
+			}
+			// a plain "if" inside a string or comment doesn't count
+			print "if";
+			return "ok";
+		}
+		"#;
+		let tokens = Tokenizer::new(source).tokenize().unwrap();
+		assert_eq!(complexity_hint(&tokens), 5);
+		assert_eq!(complexity_hint(&Tokenizer::new("print 1;").tokenize().unwrap()), 0);
+	}
+
+	#[test]
+	fn test_lint_adjacent_strings() {
+		let tokens = Tokenizer::new(r#"print "a" "b" + "c";"#).tokenize_spanned().unwrap();
+		assert_eq!(lint_adjacent_strings(&tokens), vec![LexWarning::AdjacentStrings {
+			first: Span {
+				start: 6,
+				end: 9
+			},
+			second: Span {
+				start: 10,
+				end: 13
+			}
+		}]);
+		assert_eq!(lint_adjacent_strings(&tokens)[0].to_string(), "Warning: Adjacent string literals, did you forget a '+'?");
+
+		let tokens = Tokenizer::new(r#""a" "b" "c"; "d";"#).tokenize_spanned().unwrap();
+		let spans: Vec<_> = lint_adjacent_strings(&tokens).iter().map(|warning| (warning.span().start, warning.span().end)).collect();
+		assert_eq!(spans, vec![(4, 7), (8, 11)]);
+
+		let options = TokenizerOptions::new().trivia(true);
+		let tokens = Tokenizer::new_with_options("\"a\" /* gap */\n\"b\"", options).tokenize_spanned().unwrap();
+		assert_eq!(lint_adjacent_strings(&tokens), vec![LexWarning::AdjacentStrings {
+			first: Span {
+				start: 0,
+				end: 3
+			},
+			second: Span {
+				start: 14,
+				end: 17
+			}
+		}]);
+
+		assert_eq!(lint_adjacent_strings(&Tokenizer::new(r#"print "a" + "b";"#).tokenize_spanned().unwrap()), vec![]);
+	}
+}
\ No newline at end of file
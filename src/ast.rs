@@ -0,0 +1,282 @@
+use std::borrow::Cow;
+
+use crate::tokenizer::Token;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+	Number(f64),
+	String(String),
+	Bool(bool),
+	Nil
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+	Negate,
+	Not
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+	Equal, NotEqual,
+	Greater, GreaterEqual, Less, LessEqual,
+	Add, Subtract,
+	Multiply, Divide
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+	Literal(Literal),
+	Unary {
+		op: UnaryOp,
+		right: Box<Expr>
+	},
+	Binary {
+		left: Box<Expr>,
+		op: BinaryOp,
+		right: Box<Expr>
+	},
+	Grouping(Box<Expr>),
+	Variable(String),
+	Assign {
+		name: String,
+		value: Box<Expr>
+	},
+	/// `[a, b, c]`
+	List(Vec<Expr>),
+	/// `{key: value, ...}`, with entries in source order
+	Map(Vec<(Expr, Expr)>),
+	/// `object[index]`
+	Index {
+		object: Box<Expr>,
+		index: Box<Expr>
+	}
+}
+
+/// There's no `for` statement, `for` loops are desugared into a `While` inside a `Block` like in the book
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+	Print(Expr),
+	Expression(Expr),
+	/// `assert condition;`, for Lox scripts that test themselves.  `line` is where the `assert` was, if the
+	/// tokens it was parsed from had spans.
+	Assert {
+		condition: Expr,
+		line: Option<usize>
+	},
+	Var {
+		name: String,
+		initializer: Option<Expr>
+	},
+	Block(Vec<Stmt>),
+	If {
+		condition: Expr,
+		then_branch: Box<Stmt>,
+		else_branch: Option<Box<Stmt>>
+	},
+	While {
+		condition: Expr,
+		body: Box<Stmt>
+	},
+	/// `do body while (condition);`, which runs `body` once before checking `condition`
+	DoWhile {
+		body: Box<Stmt>,
+		condition: Expr
+	}
+}
+
+impl UnaryOp {
+	pub fn symbol(self) -> &'static str {
+		match self {
+			UnaryOp::Negate => "-",
+			UnaryOp::Not => "!"
+		}
+	}
+}
+
+impl BinaryOp {
+	pub fn symbol(self) -> &'static str {
+		match self {
+			BinaryOp::Equal => "==",
+			BinaryOp::NotEqual => "!=",
+			BinaryOp::Greater => ">",
+			BinaryOp::GreaterEqual => ">=",
+			BinaryOp::Less => "<",
+			BinaryOp::LessEqual => "<=",
+			BinaryOp::Add => "+",
+			BinaryOp::Subtract => "-",
+			BinaryOp::Multiply => "*",
+			BinaryOp::Divide => "/"
+		}
+	}
+}
+
+impl std::fmt::Display for Literal {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Literal::Number(num) => write!(f, "{}", num),
+			Literal::String(value) => write!(f, "{}", Token::String(Cow::Borrowed(value))),
+			Literal::Bool(value) => write!(f, "{}", value),
+			Literal::Nil => write!(f, "nil")
+		}
+	}
+}
+
+/// Prints the expression in the book's parenthesized prefix form, e.g. `1 + 2 * 3` is `(+ 1 (* 2 3))`
+impl std::fmt::Display for Expr {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Expr::Literal(literal) => write!(f, "{}", literal),
+			Expr::Unary { op, right } => write!(f, "({} {})", op.symbol(), right),
+			Expr::Binary { left, op, right } => write!(f, "({} {} {})", op.symbol(), left, right),
+			Expr::Grouping(inner) => write!(f, "(group {})", inner),
+			Expr::Variable(name) => write!(f, "{}", name),
+			Expr::Assign { name, value } => write!(f, "(= {} {})", name, value),
+			Expr::List(elements) => {
+				write!(f, "(list")?;
+				for element in elements {
+					write!(f, " {}", element)?;
+				}
+				write!(f, ")")
+			},
+			Expr::Map(entries) => {
+				write!(f, "(map")?;
+				for (key, value) in entries {
+					write!(f, " ({} {})", key, value)?;
+				}
+				write!(f, ")")
+			},
+			Expr::Index { object, index } => write!(f, "(index {} {})", object, index)
+		}
+	}
+}
+
+/// Prints the statement like an `Expr`, with nested statements on their own lines indented by two spaces:
+///
+/// ```text
+/// (while (< i 3)
+///   (block
+///     (print i)))
+/// ```
+impl std::fmt::Display for Stmt {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		self.write_indented(f, 0)
+	}
+}
+
+impl Stmt {
+	fn write_indented(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+		let nested = |f: &mut std::fmt::Formatter<'_>, stmt: &Stmt| {
+			write!(f, "\n{}", "  ".repeat(depth + 1))?;
+			stmt.write_indented(f, depth + 1)
+		};
+
+		match self {
+			Stmt::Print(expr) => write!(f, "(print {})", expr),
+			Stmt::Expression(expr) => write!(f, "(; {})", expr),
+			Stmt::Assert { condition, .. } => write!(f, "(assert {})", condition),
+			Stmt::Var { name, initializer: Some(initializer) } => write!(f, "(var {} = {})", name, initializer),
+			Stmt::Var { name, initializer: None } => write!(f, "(var {})", name),
+			Stmt::Block(statements) => {
+				write!(f, "(block")?;
+				for stmt in statements {
+					nested(f, stmt)?;
+				}
+				write!(f, ")")
+			},
+			Stmt::If { condition, then_branch, else_branch } => {
+				write!(f, "(if {}", condition)?;
+				nested(f, then_branch)?;
+				if let Some(else_branch) = else_branch {
+					nested(f, else_branch)?;
+				}
+				write!(f, ")")
+			},
+			Stmt::While { condition, body } => {
+				write!(f, "(while {}", condition)?;
+				nested(f, body)?;
+				write!(f, ")")
+			},
+			Stmt::DoWhile { body, condition } => {
+				write!(f, "(do-while {}", condition)?;
+				nested(f, body)?;
+				write!(f, ")")
+			}
+		}
+	}
+}
+
+/// Prints each statement of a program on its own line, see `Stmt`'s `Display`
+pub fn print_program(program: &[Stmt]) -> String {
+	let mut printed = String::new();
+	for stmt in program {
+		printed.push_str(&stmt.to_string());
+		printed.push('\n');
+	}
+	printed
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parse;
+
+	fn print(source: &str) -> String {
+		print_program(&parse(source).unwrap())
+	}
+
+	#[test]
+	fn test_print_expressions() {
+		assert_eq!(print("1 + 2 * 3;"), "(; (+ 1 (* 2 3)))\n");
+		assert_eq!(print("-(1.5 - x) >= !true;"), "(; (>= (- (group (- 1.5 x))) (! true)))\n");
+		assert_eq!(print("a = b = nil == false;"), "(; (= a (= b (== nil false))))\n");
+		assert_eq!(print("[1, \"two\\n\", []][0];"), "(; (index (list 1 \"two\\n\" (list)) 0))\n");
+		assert_eq!(print("print {\"a\": 1, 2: {}};"), "(print (map (\"a\" 1) (2 (map))))\n");
+	}
+
+	#[test]
+	fn test_print_statements() {
+		let source = r#"
+		var greeting = "Hello, \"World\"";
+		var empty;
+		print greeting;
+		{
+			var x = 1;
+			{}
+		}
+		"#;
+		assert_eq!(print(source), concat!(
+			"(var greeting = \"Hello, \\\"World\\\"\")\n",
+			"(var empty)\n",
+			"(print greeting)\n",
+			"(block\n",
+			"  (var x = 1)\n",
+			"  (block))\n"
+		));
+	}
+
+	#[test]
+	fn test_print_control_flow() {
+		let source = r#"
+		for (var i = 0; i < 3; i = i + 1) print i;
+		if (done) print "yes"; else { print "no"; }
+		do print 1; while (false);
+		assert i == 3;
+		"#;
+		assert_eq!(print(source), concat!(
+			"(block\n",
+			"  (var i = 0)\n",
+			"  (while (< i 3)\n",
+			"    (block\n",
+			"      (print i)\n",
+			"      (; (= i (+ i 1))))))\n",
+			"(if done\n",
+			"  (print \"yes\")\n",
+			"  (block\n",
+			"    (print \"no\")))\n",
+			"(do-while false\n",
+			"  (print 1))\n",
+			"(assert (== i 3))\n"
+		));
+	}
+}
\ No newline at end of file
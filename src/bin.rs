@@ -1,27 +1,148 @@
-use lox_rs::tokenize;
+use std::io::Read;
+use std::process::ExitCode;
 
-fn main() {
-	// let source = if let Ok(data) = std::fs::read_to_string("src/test.lox") {
-	// 	data
-	// } else if let Ok(data) = std::fs::read_to_string("test.lox") {
-	// 	data
-	// } else {
-	// 	panic!("No test.lox file found")
-	// };
+use lox_rs::{parse, tokenize, tokenize_buffered, tokenize_lossy};
+use lox_rs::ast::print_program;
+use lox_rs::token_table::print_token_table;
+use lox_rs::tokenizer::{LexError, Token, TokenKind, Tokenizer};
 
+// Exit codes from sysexits.h, as used by the reference Lox implementations
+const EXIT_USAGE: u8 = 64;
+const EXIT_DATA_ERROR: u8 = 65;
+const EXIT_NO_INPUT: u8 = 66;
+
+const USAGE: &str = "Usage: lox [--ast | --bench] [path]
+
+Tokenizes the file at `path`, or standard input when no path is given, and prints the tokens.
+  --ast    Parse the program and print its syntax tree instead
+  --bench  Time the tokenizer on a large generated program instead";
+
+fn main() -> ExitCode {
+	let mut ast = false;
+	let mut bench = false;
+	let mut path = None;
+	for arg in std::env::args().skip(1) {
+		match arg.as_str() {
+			"--ast" => ast = true,
+			"--bench" => bench = true,
+			"-h" | "--help" => {
+				println!("{}", USAGE);
+				return ExitCode::SUCCESS;
+			},
+			_ if arg.starts_with('-') || path.is_some() => {
+				eprintln!("{}", USAGE);
+				return ExitCode::from(EXIT_USAGE);
+			},
+			_ => path = Some(arg)
+		}
+	}
+
+	if ast && bench {
+		eprintln!("{}", USAGE);
+		return ExitCode::from(EXIT_USAGE);
+	}
+	if bench {
+		return run_bench();
+	}
+
+	let source = match &path {
+		Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e)),
+		None => {
+			let mut source = String::new();
+			std::io::stdin().read_to_string(&mut source).map(|_| source).map_err(|e| format!("Could not read standard input: {}", e))
+		}
+	};
+	let source = match source {
+		Ok(source) => source,
+		Err(message) => {
+			eprintln!("{}", message);
+			return ExitCode::from(EXIT_NO_INPUT);
+		}
+	};
+
+	if ast {
+		return match parse(&source) {
+			Ok(program) => {
+				print!("{}", print_program(&program));
+				ExitCode::SUCCESS
+			},
+			Err(errors) => {
+				for e in errors {
+					println!("{}\n", e.render(&source));
+				}
+				ExitCode::from(EXIT_DATA_ERROR)
+			}
+		};
+	}
+
+	let output = tokenize_lossy(&source);
+	if !output.errors.is_empty() {
+		print_errors(&output.errors, &source);
+		return ExitCode::from(EXIT_DATA_ERROR);
+	}
+
+	print!("{}", print_token_table(&output.tokens, &source));
+	ExitCode::SUCCESS
+}
+
+fn print_errors(errors: &[LexError], source: &str) {
+	for e in errors {
+		println!("{}\n", e.render(source));
+	}
+}
+
+/// Tokenizes a large generated program three ways, timing each
+fn run_bench() -> ExitCode {
 	let mut source = String::new();
-	for _ in 1..=1_000_000 {
-		source.push_str("print \"Hello, World!\";\n");
+	for i in 1..=100_000 {
+		source.push_str(&format!("var x{} = {} + \"Hello, World!\";\nprint x{};\n", i % 100, i, i % 100));
 	}
 
 	let start = std::time::SystemTime::now();
 
-	let tokens = match tokenize(source) {
+	let tokens = match tokenize(&source) {
 		Ok(res) => res,
-		Err(e) => {
-			println!("{e}");
-			panic!();
+		Err(errors) => {
+			print_errors(&errors, &source);
+			return ExitCode::from(EXIT_DATA_ERROR);
 		}
 	};
-	println!("Tokens: {}\nTime taken: {}µs", tokens.len(), std::time::SystemTime::now().duration_since(start).unwrap().as_micros());
-}
+	// Stand-in for a parser's lookahead: walk the stream looking for statement boundaries
+	let statements = tokens.iter().filter(|token| **token == Token::Semicolon).count();
+	println!("Vec<Token>:  {} tokens, {} statements, {}µs", tokens.len(), statements, std::time::SystemTime::now().duration_since(start).unwrap().as_micros());
+
+	let start = std::time::SystemTime::now();
+
+	// Pull tokens one at a time without ever holding the whole stream in memory
+	let mut token_count: usize = 0;
+	let mut statements: usize = 0;
+	for result in Tokenizer::new(&source) {
+		match result {
+			Ok(token) => {
+				token_count += 1;
+				if token == Token::Semicolon {
+					statements += 1;
+				}
+			},
+			Err(e) => {
+				print_errors(&[e], &source);
+				return ExitCode::from(EXIT_DATA_ERROR);
+			}
+		}
+	}
+	println!("Streaming:   {} tokens, {} statements, {}µs", token_count, statements, std::time::SystemTime::now().duration_since(start).unwrap().as_micros());
+
+	let start = std::time::SystemTime::now();
+
+	let buffer = match tokenize_buffered(&source) {
+		Ok(res) => res,
+		Err(errors) => {
+			print_errors(&errors, &source);
+			return ExitCode::from(EXIT_DATA_ERROR);
+		}
+	};
+	let statements = buffer.kinds().iter().filter(|kind| **kind == TokenKind::Semicolon).count();
+	println!("TokenBuffer: {} tokens, {} statements, {}µs", buffer.len(), statements, std::time::SystemTime::now().duration_since(start).unwrap().as_micros());
+
+	ExitCode::SUCCESS
+}
\ No newline at end of file
@@ -0,0 +1,230 @@
+use crate::parser::SyntaxError;
+use crate::analysis::LexWarning;
+use crate::line_index::LineIndex;
+use crate::tokenizer::{LexError, Location};
+
+/// Tabs are expanded to this many spaces so the caret lines up however the terminal renders them
+const TAB_WIDTH: usize = 4;
+/// Longer lines are cut down to a window of this many characters around the error
+const MAX_LINE_WIDTH: usize = 100;
+
+/// Renders `message` rustc-style: a header, then the line `location` is on with a caret under its column.
+///
+/// ```text
+/// error: Invalid token '@'
+///  --> 1:9
+///   |
+/// 1 | var a = @;
+///   |         ^
+/// ```
+pub fn render(source: &str, location: Location, message: &str) -> String {
+	render_with_level(source, location, "error", message)
+}
+
+fn render_with_level(source: &str, location: Location, level: &str, message: &str) -> String {
+	let line_text = line_around(source, location.offset);
+
+	let mut text: Vec<char> = Vec::new();
+	let mut caret = None;
+	for (i, c) in line_text.chars().enumerate() {
+		if i + 1 == location.column {
+			caret = Some(text.len());
+		}
+		if c == '\t' {
+			text.extend(std::iter::repeat_n(' ', TAB_WIDTH));
+		} else {
+			text.push(c);
+		}
+	}
+	// An error at the very end of the line points just past its last character
+	let mut caret = caret.unwrap_or(text.len());
+
+	if text.len() > MAX_LINE_WIDTH {
+		let end = (caret.saturating_sub(MAX_LINE_WIDTH / 2) + MAX_LINE_WIDTH).min(text.len());
+		let start = end - MAX_LINE_WIDTH;
+		let mut window: Vec<char> = Vec::new();
+		if start > 0 {
+			window.extend("...".chars());
+		}
+		caret = caret - start + window.len();
+		window.extend(&text[start..end]);
+		if end < text.len() {
+			window.extend("...".chars());
+		}
+		text = window;
+	}
+
+	let line_number = location.line.to_string();
+	let gutter = " ".repeat(line_number.len());
+	let text: String = text.into_iter().collect();
+
+	let mut rendered = format!("{}: {}\n", level, message);
+	rendered.push_str(&format!("{}--> {}:{}\n", gutter, location.line, location.column));
+	rendered.push_str(&format!("{} |\n", gutter));
+	rendered.push_str(format!("{} | {}", line_number, text).trim_end());
+	rendered.push('\n');
+	rendered.push_str(&format!("{} | {}^", gutter, " ".repeat(caret)));
+	rendered
+}
+
+/// The text of the line containing `offset`, without its line ending.  Only looks at that line of the source
+/// so rendering every error in a big file stays linear.
+fn line_around(source: &str, offset: usize) -> &str {
+	let start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+	let end = source[offset..].find('\n').map_or(source.len(), |i| offset + i);
+	source[start..end].strip_suffix('\r').unwrap_or(&source[start..end])
+}
+
+impl LexError {
+	/// Renders the error along with the source line it's on, falling back to just the message for errors
+	/// without a location
+	pub fn render(&self, source: &str) -> String {
+		match self.location() {
+			Some(location) => render(source, location, &self.message()),
+			None => format!("error: {}", self.message())
+		}
+	}
+}
+
+impl LexWarning {
+	/// Like `LexError::render`, but headed `warning:`.  Warnings only know their span, so this takes a
+	/// `LineIndex` of the source to find the line and column, which can be shared between warnings.
+	pub fn render(&self, line_index: &LineIndex) -> String {
+		let offset = self.span().start as usize;
+		let (line, column) = line_index.line_col(offset);
+		let location = Location {
+			offset,
+			line,
+			column
+		};
+		render_with_level(line_index.source(), location, "warning", &self.message())
+	}
+}
+
+impl SyntaxError {
+	/// Like `LexError::render`
+	pub fn render(&self, source: &str) -> String {
+		match self.location() {
+			Some(location) => render(source, location, &self.message()),
+			None => format!("error: {}", self.message())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::line_index::LineIndex;
+	use crate::tokenizer::Tokenizer;
+
+	fn render_first_error(source: &str) -> String {
+		Tokenizer::new(source).tokenize_lossy().errors[0].render(source)
+	}
+
+	#[test]
+	fn test_mid_line_error() {
+		assert_eq!(render_first_error("var a = 1;\nvar b = @;"), [
+			"error: Invalid token '@'",
+			" --> 2:9",
+			"  |",
+			"2 | var b = @;",
+			"  |         ^"
+		].join("\n"));
+	}
+
+	#[test]
+	fn test_first_column_error() {
+		assert_eq!(render_first_error("@"), [
+			"error: Invalid token '@'",
+			" --> 1:1",
+			"  |",
+			"1 | @",
+			"  | ^"
+		].join("\n"));
+	}
+
+	#[test]
+	fn test_unterminated_string_across_lines() {
+		let source = "\n\n\n\n\n\n\n\n\nprint \"abc\ndef;\n";
+		assert_eq!(render_first_error(source), [
+			"error: Unterminated string",
+			"  --> 10:7",
+			"   |",
+			"10 | print \"abc",
+			"   |       ^"
+		].join("\n"));
+	}
+
+	#[test]
+	fn test_tabs_are_expanded() {
+		assert_eq!(render_first_error("\tprint\t@;"), [
+			"error: Invalid token '@'",
+			" --> 1:8",
+			"  |",
+			"1 |     print    @;",
+			"  |              ^"
+		].join("\n"));
+	}
+
+	#[test]
+	fn test_long_lines_are_truncated() {
+		let source = format!("var a = \"{}\"; @ var b = \"{}\";", "x".repeat(200), "y".repeat(200));
+		let rendered = render_first_error(&source);
+		let lines: Vec<&str> = rendered.lines().collect();
+
+		assert!(lines[3].starts_with("1 | ...xxx"));
+		assert!(lines[3].ends_with("yyy..."));
+		let caret = lines[4].find('^').unwrap();
+		assert_eq!(&lines[3][caret..caret + 1], "@");
+	}
+
+	#[test]
+	fn test_error_without_location() {
+		let error = crate::tokenizer::LexError::SourceTooLarge {
+			len: 5_000_000_000
+		};
+		assert_eq!(error.render(""), "error: Source too large (5000000000 bytes, the maximum is 4294967295 bytes)");
+	}
+
+	#[test]
+	fn test_many_trailing_errors_render_quickly() {
+		// Small enough to take a few milliseconds, but rescanning the source for each error would take seconds
+		let source = "print 1;\n".repeat(20_000) + &"@\r\n".repeat(2_000);
+		let errors = Tokenizer::new(&source).tokenize_lossy().errors;
+		assert_eq!(errors.len(), 2_000);
+
+		let started = std::time::Instant::now();
+		let rendered: Vec<String> = errors.iter().map(|e| e.render(&source)).collect();
+		assert!(started.elapsed() < std::time::Duration::from_secs(1));
+		assert_eq!(rendered[1_999], [
+			"error: Invalid token '@'",
+			"     --> 22000:1",
+			"      |",
+			"22000 | @",
+			"      | ^"
+		].join("\n"));
+	}
+
+	#[test]
+	fn test_warning() {
+		let source = "print \"a\"\n\t\"b\";\nprint \"c\" \"d\";";
+		let tokens = Tokenizer::new(source).tokenize_spanned().unwrap();
+		let line_index = LineIndex::new(source);
+		let rendered: Vec<String> = crate::analysis::lint_adjacent_strings(&tokens).iter().map(|warning| warning.render(&line_index)).collect();
+		assert_eq!(rendered, [
+			[
+				"warning: Adjacent string literals, did you forget a '+'?",
+				" --> 2:2",
+				"  |",
+				"2 |     \"b\";",
+				"  |     ^"
+			].join("\n"),
+			[
+				"warning: Adjacent string literals, did you forget a '+'?",
+				" --> 3:11",
+				"  |",
+				"3 | print \"c\" \"d\";",
+				"  |           ^"
+			].join("\n")
+		]);
+	}
+}
\ No newline at end of file
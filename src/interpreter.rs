@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::ast::{BinaryOp, Expr, Literal, Stmt, UnaryOp};
+
+#[derive(Debug, Clone)]
+pub enum Value {
+	Number(f64),
+	Str(String),
+	Bool(bool),
+	Nil,
+	List(Vec<Value>),
+	/// Entries in insertion order.  Values can't be hashed (numbers are `f64`) so lookups are linear.
+	Map(Vec<(Value, Value)>)
+}
+
+impl Value {
+	/// Only `nil` and `false` are falsey
+	pub fn is_truthy(&self) -> bool {
+		!matches!(self, Value::Nil | Value::Bool(false))
+	}
+}
+
+/// Looks up `key` in a map's entries
+fn map_get<'a>(entries: &'a [(Value, Value)], key: &Value) -> Option<&'a Value> {
+	entries.iter().find(|(existing, _)| existing == key).map(|(_, value)| value)
+}
+
+/// Maps are equal when they have the same entries, whatever order they were inserted in
+impl PartialEq for Value {
+	fn eq(&self, other: &Value) -> bool {
+		match (self, other) {
+			(Value::Number(left), Value::Number(right)) => left == right,
+			(Value::Str(left), Value::Str(right)) => left == right,
+			(Value::Bool(left), Value::Bool(right)) => left == right,
+			(Value::Nil, Value::Nil) => true,
+			(Value::List(left), Value::List(right)) => left == right,
+			// Keys are unique within a map, so matching lengths and lookups means the same entries
+			(Value::Map(left), Value::Map(right)) => {
+				left.len() == right.len() && left.iter().all(|(key, value)| map_get(right, key) == Some(value))
+			},
+			_ => false
+		}
+	}
+}
+
+impl std::fmt::Display for Value {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Value::Number(num) => write!(f, "{}", num),
+			Value::Str(value) => write!(f, "{}", value),
+			Value::Bool(value) => write!(f, "{}", value),
+			Value::Nil => write!(f, "nil"),
+			Value::List(elements) => {
+				write!(f, "[")?;
+				for (i, element) in elements.iter().enumerate() {
+					if i > 0 {
+						write!(f, ", ")?;
+					}
+					write!(f, "{}", element)?;
+				}
+				write!(f, "]")
+			},
+			Value::Map(entries) => {
+				write!(f, "{{")?;
+				for (i, (key, value)) in entries.iter().enumerate() {
+					if i > 0 {
+						write!(f, ", ")?;
+					}
+					write!(f, "{}: {}", key, value)?;
+				}
+				write!(f, "}}")
+			}
+		}
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RuntimeError {
+	pub message: String,
+	/// The source line the error happened on, when the statement knows it (only `assert` does so far)
+	pub line: Option<usize>
+}
+
+impl RuntimeError {
+	fn new(message: impl Into<String>) -> RuntimeError {
+		RuntimeError {
+			message: message.into(),
+			line: None
+		}
+	}
+}
+
+impl std::fmt::Display for RuntimeError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.line {
+			Some(line) => write!(f, "[line {}] Runtime error: {}", line, self.message),
+			None => write!(f, "Runtime error: {}", self.message)
+		}
+	}
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Evaluates an expression on its own, so using a variable is an error
+pub fn eval(expr: &Expr) -> Result<Value, RuntimeError> {
+	Interpreter::new(std::io::sink()).evaluate(expr)
+}
+
+/// Runs programs, writing whatever they `print` to `out`.  Variables live on between calls to `run`.
+pub struct Interpreter<W> {
+	// Innermost block last, with the globals at the bottom
+	scopes: Vec<HashMap<String, Value>>,
+	out: W
+}
+
+impl<W: Write> Interpreter<W> {
+	pub fn new(out: W) -> Interpreter<W> {
+		Interpreter {
+			scopes: vec![HashMap::new()],
+			out
+		}
+	}
+
+	/// Runs each statement in turn, stopping at the first runtime error
+	pub fn run(&mut self, program: &[Stmt]) -> Result<(), RuntimeError> {
+		program.iter().try_for_each(|stmt| self.execute(stmt))
+	}
+
+	pub fn into_output(self) -> W {
+		self.out
+	}
+
+	fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+		match stmt {
+			Stmt::Print(expr) => {
+				let value = self.evaluate(expr)?;
+				writeln!(self.out, "{}", value).map_err(|e| RuntimeError::new(format!("Could not write output: {}", e)))?;
+			},
+			Stmt::Expression(expr) => {
+				self.evaluate(expr)?;
+			},
+			Stmt::Assert { condition, line } => {
+				if !self.evaluate(condition)?.is_truthy() {
+					return Err(RuntimeError {
+						message: String::from("Assertion failed"),
+						line: *line
+					});
+				}
+			},
+			Stmt::Var { name, initializer } => {
+				let value = match initializer {
+					Some(initializer) => self.evaluate(initializer)?,
+					None => Value::Nil
+				};
+				self.scopes.last_mut().unwrap().insert(name.clone(), value);
+			},
+			Stmt::Block(statements) => {
+				self.scopes.push(HashMap::new());
+				let result = self.run(statements);
+				self.scopes.pop();
+				result?;
+			},
+			Stmt::If { condition, then_branch, else_branch } => {
+				if self.evaluate(condition)?.is_truthy() {
+					self.execute(then_branch)?;
+				} else if let Some(else_branch) = else_branch {
+					self.execute(else_branch)?;
+				}
+			},
+			Stmt::While { condition, body } => {
+				while self.evaluate(condition)?.is_truthy() {
+					self.execute(body)?;
+				}
+			},
+			Stmt::DoWhile { body, condition } => loop {
+				self.execute(body)?;
+				if !self.evaluate(condition)?.is_truthy() {
+					break;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn evaluate(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
+		Ok(match expr {
+			Expr::Literal(literal) => match literal {
+				Literal::Number(num) => Value::Number(*num),
+				Literal::String(value) => Value::Str(value.clone()),
+				Literal::Bool(value) => Value::Bool(*value),
+				Literal::Nil => Value::Nil
+			},
+			Expr::Grouping(inner) => self.evaluate(inner)?,
+			Expr::Unary { op, right } => {
+				let right = self.evaluate(right)?;
+				match op {
+					UnaryOp::Not => Value::Bool(!right.is_truthy()),
+					UnaryOp::Negate => match right {
+						Value::Number(num) => Value::Number(-num),
+						_ => return Err(RuntimeError::new("Operand must be a number"))
+					}
+				}
+			},
+			Expr::Binary { left, op, right } => binary(self.evaluate(left)?, *op, self.evaluate(right)?)?,
+			Expr::List(elements) => Value::List(elements.iter().map(|element| self.evaluate(element)).collect::<Result<_, _>>()?),
+			Expr::Map(entries) => {
+				// A repeated key keeps its first position but takes the last value
+				let mut map: Vec<(Value, Value)> = Vec::with_capacity(entries.len());
+				for (key, value) in entries {
+					let key = self.evaluate(key)?;
+					let value = self.evaluate(value)?;
+					match map.iter_mut().find(|(existing, _)| *existing == key) {
+						Some(entry) => entry.1 = value,
+						None => map.push((key, value))
+					}
+				}
+				Value::Map(map)
+			},
+			Expr::Index { object, index } => {
+				let mut elements = match self.evaluate(object)? {
+					Value::List(elements) => elements,
+					Value::Map(entries) => {
+						let key = self.evaluate(index)?;
+						return match map_get(&entries, &key) {
+							Some(value) => Ok(value.clone()),
+							None => Err(RuntimeError::new(format!("Key '{}' is not in the map", key)))
+						};
+					},
+					_ => return Err(RuntimeError::new("Only lists and maps can be indexed"))
+				};
+				let Value::Number(index) = self.evaluate(index)? else {
+					return Err(RuntimeError::new("List index must be a number"));
+				};
+				if index.fract() != 0.0 || index < 0.0 || index >= elements.len() as f64 {
+					return Err(RuntimeError::new(format!("List index {} is out of bounds for a list of length {}", index, elements.len())));
+				}
+				elements.swap_remove(index as usize)
+			},
+			Expr::Variable(name) => match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+				Some(value) => value.clone(),
+				None => return Err(undefined(name))
+			},
+			Expr::Assign { name, value } => {
+				let value = self.evaluate(value)?;
+				match self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+					Some(variable) => *variable = value.clone(),
+					None => return Err(undefined(name))
+				}
+				value
+			}
+		})
+	}
+}
+
+fn undefined(name: &str) -> RuntimeError {
+	RuntimeError::new(format!("Undefined variable '{}'", name))
+}
+
+fn binary(left: Value, op: BinaryOp, right: Value) -> Result<Value, RuntimeError> {
+	match op {
+		BinaryOp::Equal => return Ok(Value::Bool(left == right)),
+		BinaryOp::NotEqual => return Ok(Value::Bool(left != right)),
+		BinaryOp::Add => match (left, right) {
+			(Value::Str(left), Value::Str(right)) => return Ok(Value::Str(left + &right)),
+			(Value::Number(left), Value::Number(right)) => return Ok(Value::Number(left + right)),
+			_ => return Err(RuntimeError::new("Operands must be two numbers or two strings"))
+		},
+		_ => {}
+	}
+
+	let (Value::Number(left), Value::Number(right)) = (left, right) else {
+		return Err(RuntimeError::new("Operands must be numbers"));
+	};
+
+	Ok(match op {
+		BinaryOp::Subtract => Value::Number(left - right),
+		BinaryOp::Multiply => Value::Number(left * right),
+		BinaryOp::Divide => {
+			if right == 0.0 {
+				return Err(RuntimeError::new("Division by zero"));
+			}
+			Value::Number(left / right)
+		},
+		BinaryOp::Greater => Value::Bool(left > right),
+		BinaryOp::GreaterEqual => Value::Bool(left >= right),
+		BinaryOp::Less => Value::Bool(left < right),
+		BinaryOp::LessEqual => Value::Bool(left <= right),
+		BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Add => unreachable!()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{parse_expression, tokenize};
+
+	fn run(source: &str) -> Result<Value, RuntimeError> {
+		eval(&parse_expression(tokenize(source).unwrap()).unwrap())
+	}
+
+	#[test]
+	fn test_arithmetic() {
+		assert_eq!(run("(1 + 2) * 3"), Ok(Value::Number(9.0)));
+		assert_eq!(run("10 - 4 / 2"), Ok(Value::Number(8.0)));
+		assert_eq!(run("-(2 * 3)"), Ok(Value::Number(-6.0)));
+	}
+
+	#[test]
+	fn test_strings() {
+		assert_eq!(run("\"a\" + \"b\""), Ok(Value::Str(String::from("ab"))));
+		assert_eq!(run("\"a\" + 1"), Err(RuntimeError::new("Operands must be two numbers or two strings")));
+	}
+
+	#[test]
+	fn test_truthiness_and_equality() {
+		assert_eq!(run("!nil"), Ok(Value::Bool(true)));
+		assert_eq!(run("!0"), Ok(Value::Bool(false)));
+		assert_eq!(run("!\"\""), Ok(Value::Bool(false)));
+		assert_eq!(run("1 == \"1\""), Ok(Value::Bool(false)));
+		assert_eq!(run("nil == nil"), Ok(Value::Bool(true)));
+		assert_eq!(run("\"a\" != \"b\""), Ok(Value::Bool(true)));
+		assert_eq!(run("2 >= 2"), Ok(Value::Bool(true)));
+	}
+
+	#[test]
+	fn test_errors() {
+		assert_eq!(run("1 / 0"), Err(RuntimeError::new("Division by zero")));
+		assert_eq!(run("-true"), Err(RuntimeError::new("Operand must be a number")));
+		assert_eq!(run("1 < \"2\""), Err(RuntimeError::new("Operands must be numbers")));
+		assert_eq!(run("x").unwrap_err().to_string(), "Runtime error: Undefined variable 'x'");
+	}
+
+	#[test]
+	fn test_display() {
+		assert_eq!(run("1 + 2").unwrap().to_string(), "3");
+		assert_eq!(run("1 / 4").unwrap().to_string(), "0.25");
+		assert_eq!(run("nil").unwrap().to_string(), "nil");
+	}
+
+	#[test]
+	fn test_lists() {
+		assert_eq!(run("[1, \"two\", nil]"), Ok(Value::List(vec![
+			Value::Number(1.0),
+			Value::Str(String::from("two")),
+			Value::Nil
+		])));
+		assert_eq!(run("[1, 2 + 3][1]"), Ok(Value::Number(5.0)));
+		assert_eq!(run("[[1], [2, 3]][1][0]"), Ok(Value::Number(2.0)));
+		assert_eq!(run("[1, [2, 3], []]").unwrap().to_string(), "[1, [2, 3], []]");
+		assert_eq!(run("[1] == [1]"), Ok(Value::Bool(true)));
+		assert_eq!(run("[1, 2][2]"), Err(RuntimeError::new("List index 2 is out of bounds for a list of length 2")));
+		assert_eq!(run("[1, 2][0.5]").unwrap_err().message, "List index 0.5 is out of bounds for a list of length 2");
+		assert_eq!(run("\"ab\"[0]"), Err(RuntimeError::new("Only lists and maps can be indexed")));
+	}
+
+	#[test]
+	fn test_maps() {
+		assert_eq!(run("{\"a\": 1, \"b\": 1 + 1}"), Ok(Value::Map(vec![
+			(Value::Str(String::from("a")), Value::Number(1.0)),
+			(Value::Str(String::from("b")), Value::Number(2.0))
+		])));
+		assert_eq!(run("{1: \"one\", nil: [], 1: \"uno\"}").unwrap().to_string(), "{1: uno, nil: []}");
+		assert_eq!(run("{}").unwrap().to_string(), "{}");
+		assert_eq!(run("{1: 2} == {1: 2}"), Ok(Value::Bool(true)));
+		assert_eq!(run("{1: 2, 3: 4} == {3: 4, 1: 2}"), Ok(Value::Bool(true)));
+		assert_eq!(run("{1: 2, 3: 4} == {1: 2, 3: 5}"), Ok(Value::Bool(false)));
+		assert_eq!(run("{1: 2} == {1: 2, 3: 4}"), Ok(Value::Bool(false)));
+		assert_eq!(run("{\"a\": 1, \"b\": [2]}[\"b\"][0]"), Ok(Value::Number(2.0)));
+		assert_eq!(run("{[1]: \"list key\"}[[1]]"), Ok(Value::Str(String::from("list key"))));
+		assert_eq!(run("{\"a\": 1}[\"b\"]"), Err(RuntimeError::new("Key 'b' is not in the map")));
+		assert_eq!(run("{x: 1}"), Err(RuntimeError::new("Undefined variable 'x'")));
+	}
+
+	fn execute(source: &str) -> (Result<(), RuntimeError>, String) {
+		let mut interpreter = Interpreter::new(Vec::new());
+		let result = interpreter.run(&crate::parse(source).unwrap());
+		(result, String::from_utf8(interpreter.into_output()).unwrap())
+	}
+
+	#[test]
+	fn test_statements() {
+		let source = r#"
+		var a = 1;
+		var b;
+		{
+			var a = "inner";
+			print a;
+			b = a + "!";
+		}
+		print a;
+		print b;
+		var i = 0;
+		while (i < 3) {
+			if (i == 1) print "one"; else print i;
+			i = i + 1;
+		}
+		for (var j = 0; j < 2; j = j + 1) print [j, {"j": j}];
+		"#;
+		assert_eq!(execute(source), (Ok(()), String::from("inner\n1\ninner!\n0\none\n2\n[0, {j: 0}]\n[1, {j: 1}]\n")));
+
+		let (result, output) = execute("print 1;\n{ var x = 2; }\nprint x;");
+		assert_eq!(result, Err(RuntimeError::new("Undefined variable 'x'")));
+		assert_eq!(output, "1\n");
+		assert_eq!(execute("y = 1;").0, Err(RuntimeError::new("Undefined variable 'y'")));
+	}
+
+	#[test]
+	fn test_assert() {
+		assert_eq!(execute("var assertion = 1 + 1 == 2;\nassert assertion;\nprint \"ok\";"), (Ok(()), String::from("ok\n")));
+
+		let (result, output) = execute("assert true;\n\tassert nil;\nprint \"unreachable\";");
+		assert_eq!(result, Err(RuntimeError {
+			message: String::from("Assertion failed"),
+			line: Some(2)
+		}));
+		assert_eq!(result.unwrap_err().to_string(), "[line 2] Runtime error: Assertion failed");
+		assert_eq!(output, "");
+	}
+
+	#[test]
+	fn test_do_while() {
+		// The body runs once even though the condition starts out false
+		assert_eq!(execute("var done = true;\ndo { print \"once\"; } while (!done);"), (Ok(()), String::from("once\n")));
+		assert_eq!(execute("var i = 0;\ndo { i = i + 1; print i; } while (i < 3);"), (Ok(()), String::from("1\n2\n3\n")));
+	}
+}
\ No newline at end of file
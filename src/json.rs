@@ -0,0 +1,341 @@
+//! A stable JSON form for token streams, written by hand since the crate has no dependencies.
+//!
+//! Each token is an object whose `type` is its `TokenKind` name, with a `value` for tokens that carry a
+//! payload: `[{"type":"Print"},{"type":"String","value":"hi"},{"type":"Semicolon"},{"type":"Eof"}]`.
+//! Numbers are written in their shortest round-tripping form.
+
+use std::borrow::Cow;
+
+use crate::tokenizer::{Token, TokenKind};
+
+#[derive(Debug, PartialEq)]
+pub struct JsonError {
+	pub message: String,
+	/// Byte offset into the JSON text
+	pub offset: usize
+}
+
+impl std::fmt::Display for JsonError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} at byte {}", self.message, self.offset)
+	}
+}
+
+impl std::error::Error for JsonError {}
+
+/// # Panics
+///
+/// If a `Number` is infinite or NaN, which JSON can't represent.  The tokenizer never produces one.
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+	let mut json = String::from("[");
+
+	for (i, token) in tokens.iter().enumerate() {
+		if i > 0 {
+			json.push(',');
+		}
+		json.push_str(&format!("{{\"type\":\"{:?}\"", token.kind()));
+		match token {
+			Token::Identifier(text) | Token::String(text) | Token::Comment(text) | Token::Whitespace(text) => {
+				json.push_str(",\"value\":");
+				write_string(&mut json, text);
+			},
+			Token::Number(num) => {
+				assert!(num.is_finite(), "Can't write the non-finite number {} as JSON", num);
+				json.push_str(&format!(",\"value\":{}", num));
+			},
+			_ => {}
+		}
+		json.push('}');
+	}
+
+	json.push(']');
+	json
+}
+
+fn write_string(json: &mut String, value: &str) {
+	json.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => json.push_str("\\\""),
+			'\\' => json.push_str("\\\\"),
+			'\n' => json.push_str("\\n"),
+			'\r' => json.push_str("\\r"),
+			'\t' => json.push_str("\\t"),
+			c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+			c => json.push(c)
+		}
+	}
+	json.push('"');
+}
+
+/// Reads back the output of `tokens_to_json`.  Object members may come in any order and whitespace is
+/// allowed anywhere JSON allows it.
+pub fn tokens_from_json(json: &str) -> Result<Vec<Token<'static>>, JsonError> {
+	let mut reader = Reader {
+		json,
+		offset: 0
+	};
+	let mut tokens = Vec::new();
+
+	reader.expect('[')?;
+	if !reader.eat(']') {
+		loop {
+			tokens.push(reader.token()?);
+			if reader.eat(']') {
+				break;
+			}
+			reader.expect(',')?;
+		}
+	}
+
+	reader.skip_whitespace();
+	if reader.offset != json.len() {
+		return Err(reader.error("Unexpected text after the token list"));
+	}
+
+	Ok(tokens)
+}
+
+enum JsonValue {
+	String(String),
+	Number(f64)
+}
+
+struct Reader<'a> {
+	json: &'a str,
+	offset: usize
+}
+
+impl Reader<'_> {
+	fn error(&self, message: &str) -> JsonError {
+		JsonError {
+			message: message.to_string(),
+			offset: self.offset
+		}
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.json[self.offset..].chars().next()
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(c @ (' ' | '\t' | '\n' | '\r')) = self.peek() {
+			self.offset += c.len_utf8();
+		}
+	}
+
+	fn eat(&mut self, expected: char) -> bool {
+		self.skip_whitespace();
+		if self.peek() == Some(expected) {
+			self.offset += 1;
+			return true;
+		}
+		false
+	}
+
+	fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+		if !self.eat(expected) {
+			return Err(self.error(&format!("Expected '{}'", expected)));
+		}
+		Ok(())
+	}
+
+	fn token(&mut self) -> Result<Token<'static>, JsonError> {
+		let start = self.offset;
+		let mut kind = None;
+		let mut value = None;
+
+		self.expect('{')?;
+		if !self.eat('}') {
+			loop {
+				self.skip_whitespace();
+				let key = self.string()?;
+				self.expect(':')?;
+				match key.as_str() {
+					"type" => {
+						self.skip_whitespace();
+						let name = self.string()?;
+						kind = Some(TokenKind::ALL.into_iter()
+							.find(|kind| format!("{:?}", kind) == name)
+							.ok_or_else(|| self.error(&format!("Unknown token type '{}'", name)))?);
+					},
+					"value" => value = Some(self.value()?),
+					_ => return Err(self.error(&format!("Unknown key '{}'", key)))
+				}
+				if self.eat('}') {
+					break;
+				}
+				self.expect(',')?;
+			}
+		}
+
+		let error = |message: &str| JsonError {
+			message: message.to_string(),
+			offset: start
+		};
+		let kind = kind.ok_or_else(|| error("Token is missing its type"))?;
+		Ok(match (kind, value) {
+			(TokenKind::Identifier, Some(JsonValue::String(text))) => Token::Identifier(Cow::Owned(text)),
+			(TokenKind::String, Some(JsonValue::String(text))) => Token::String(Cow::Owned(text)),
+			(TokenKind::Comment, Some(JsonValue::String(text))) => Token::Comment(Cow::Owned(text)),
+			(TokenKind::Whitespace, Some(JsonValue::String(text))) => Token::Whitespace(Cow::Owned(text)),
+			(TokenKind::Number, Some(JsonValue::Number(num))) => Token::Number(num),
+			(kind, None) => kind.fixed_token().ok_or_else(|| error("Token is missing its value"))?,
+			_ => return Err(error("Token has the wrong kind of value"))
+		})
+	}
+
+	fn value(&mut self) -> Result<JsonValue, JsonError> {
+		self.skip_whitespace();
+		if self.peek() == Some('"') {
+			return Ok(JsonValue::String(self.string()?));
+		}
+
+		let start = self.offset;
+		while let Some(c @ ('0'..='9' | '-' | '+' | '.' | 'e' | 'E')) = self.peek() {
+			self.offset += c.len_utf8();
+		}
+		match self.json[start..self.offset].parse::<f64>() {
+			// Something like `1e999` parses as infinity, which no scanned token could hold
+			Ok(num) if num.is_finite() => Ok(JsonValue::Number(num)),
+			Ok(_) => Err(JsonError {
+				message: String::from("Number is out of range"),
+				offset: start
+			}),
+			Err(_) => Err(JsonError {
+				message: String::from("Expected a string or a number"),
+				offset: start
+			})
+		}
+	}
+
+	fn string(&mut self) -> Result<String, JsonError> {
+		if self.peek() != Some('"') {
+			return Err(self.error("Expected a string"));
+		}
+		self.offset += 1;
+
+		let mut value = String::new();
+		loop {
+			let Some(c) = self.peek() else {
+				return Err(self.error("Unterminated string"));
+			};
+			self.offset += c.len_utf8();
+			match c {
+				'"' => return Ok(value),
+				'\\' => value.push(self.escape()?),
+				c => value.push(c)
+			}
+		}
+	}
+
+	fn escape(&mut self) -> Result<char, JsonError> {
+		let Some(c) = self.peek() else {
+			return Err(self.error("Unterminated string"));
+		};
+		self.offset += c.len_utf8();
+		Ok(match c {
+			'"' => '"',
+			'\\' => '\\',
+			'/' => '/',
+			'b' => '\u{8}',
+			'f' => '\u{c}',
+			'n' => '\n',
+			'r' => '\r',
+			't' => '\t',
+			'u' => {
+				let high = self.hex4()?;
+				if !(0xD800..0xDC00).contains(&high) {
+					return char::from_u32(high).ok_or_else(|| self.error("Invalid unicode escape"));
+				}
+				// Characters outside the BMP are written as a surrogate pair
+				if !self.json[self.offset..].starts_with("\\u") {
+					return Err(self.error("Unpaired surrogate in unicode escape"));
+				}
+				self.offset += 2;
+				let low = self.hex4()?;
+				if !(0xDC00..0xE000).contains(&low) {
+					return Err(self.error("Unpaired surrogate in unicode escape"));
+				}
+				char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).unwrap()
+			},
+			_ => return Err(self.error("Invalid escape sequence"))
+		})
+	}
+
+	fn hex4(&mut self) -> Result<u32, JsonError> {
+		let digits = self.json.get(self.offset..self.offset + 4)
+			.filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+			.ok_or_else(|| self.error("Expected four hex digits"))?;
+		self.offset += 4;
+		Ok(u32::from_str_radix(digits, 16).unwrap())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tokenize;
+	use crate::tokenizer::{Tokenizer, TokenizerOptions};
+
+	#[test]
+	fn test_to_json() {
+		assert_eq!(
+			tokens_to_json(&tokenize("print \"hi\";").unwrap()),
+			r#"[{"type":"Print"},{"type":"String","value":"hi"},{"type":"Semicolon"},{"type":"Eof"}]"#
+		);
+		assert_eq!(
+			tokens_to_json(&tokenize("x = 0.1 + 2;").unwrap()),
+			r#"[{"type":"Identifier","value":"x"},{"type":"Equal"},{"type":"Number","value":0.1},{"type":"Plus"},{"type":"Number","value":2},{"type":"Semicolon"},{"type":"Eof"}]"#
+		);
+		assert_eq!(tokens_to_json(&[]), "[]");
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let source = r#"
+		var ratio = 0.1 + 1.234_567 * 0x7FFF_FFFF_FFFF;
+		print "quote \" backslash \\ tab \t nul \0 bell \x07 emoji \u{1F600} é";
+		class A < B { init() { this.x = super.y; } }
+		"#;
+		let tokens = tokenize(source).unwrap();
+		assert_eq!(tokens_from_json(&tokens_to_json(&tokens)), Ok(tokens));
+
+		let options = TokenizerOptions::new().trivia(true);
+		let tokens = Tokenizer::new_with_options("// hi\n\tprint 1; /* bye */", options).tokenize().unwrap();
+		assert_eq!(tokens_from_json(&tokens_to_json(&tokens)), Ok(tokens));
+	}
+
+	#[test]
+	fn test_from_json() {
+		let json = r#"
+		[
+			{ "value": "aé😀\/", "type": "String" },
+			{ "type": "Number", "value": 1.5e3 }
+		]
+		"#;
+		assert_eq!(tokens_from_json(json), Ok(vec![
+			Token::String(Cow::from("aé😀/")),
+			Token::Number(1500.0)
+		]));
+	}
+
+	#[test]
+	fn test_from_json_errors() {
+		assert_eq!(tokens_from_json(r#"[{"type":"Bogus"}]"#).unwrap_err().message, "Unknown token type 'Bogus'");
+		assert_eq!(tokens_from_json(r#"[{"type":"String"}]"#), Err(JsonError {
+			message: String::from("Token is missing its value"),
+			offset: 1
+		}));
+		assert_eq!(tokens_from_json(r#"[{"type":"Number","value":"1"}]"#).unwrap_err().message, "Token has the wrong kind of value");
+		assert_eq!(tokens_from_json(r#"[{"type":"Eof"}"#).unwrap_err().to_string(), "Expected ',' at byte 15");
+		assert_eq!(tokens_from_json("[] []").unwrap_err().message, "Unexpected text after the token list");
+		assert_eq!(tokens_from_json(r#"[{"type":"Number","value":-1e999}]"#).unwrap_err().to_string(), "Number is out of range at byte 26");
+	}
+
+	#[test]
+	#[should_panic(expected = "Can't write the non-finite number inf as JSON")]
+	fn test_to_json_non_finite() {
+		tokens_to_json(&[Token::Number(f64::INFINITY)]);
+	}
+}
\ No newline at end of file
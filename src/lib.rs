@@ -1,8 +1,57 @@
 pub mod tokenizer;
+pub mod token_buffer;
+pub mod pack;
+pub mod analysis;
+pub mod token_table;
+pub mod line_index;
+pub mod diagnostic;
+pub mod ast;
+pub mod parser;
+pub mod interpreter;
+pub mod json;
 
-use tokenizer::{Tokenizer, Token};
+use tokenizer::{Tokenizer, Token, Spanned, LexError, LexOutput};
+use token_buffer::TokenBuffer;
+use ast::{Expr, Stmt};
+use parser::{Parser, ParseError, SyntaxError};
 
-pub fn tokenize(source: String) -> Result<Vec<Token>, String> {
-	let mut tokenizer = Tokenizer::new(source);
+pub fn tokenize<S: AsRef<str> + ?Sized>(source: &S) -> Result<Vec<Token<'_>>, Vec<LexError>> {
+	let mut tokenizer = Tokenizer::new(source.as_ref());
 	tokenizer.tokenize()
+}
+
+pub fn tokenize_spanned<S: AsRef<str> + ?Sized>(source: &S) -> Result<Vec<Spanned<Token<'_>>>, Vec<LexError>> {
+	let mut tokenizer = Tokenizer::new(source.as_ref());
+	tokenizer.tokenize_spanned()
+}
+
+pub fn tokenize_lossy<S: AsRef<str> + ?Sized>(source: &S) -> LexOutput<'_> {
+	let mut tokenizer = Tokenizer::new(source.as_ref());
+	tokenizer.tokenize_lossy()
+}
+
+pub fn tokenize_buffered<S: AsRef<str> + ?Sized>(source: &S) -> Result<TokenBuffer, Vec<LexError>> {
+	let mut tokenizer = Tokenizer::new(source.as_ref());
+	tokenizer.tokenize_buffered()
+}
+
+pub fn parse_expression(tokens: Vec<Token<'_>>) -> Result<Expr, ParseError> {
+	Parser::new(tokens).parse_expression()
+}
+
+/// `tokens` must have been scanned from `source`, which is used to give errors their locations
+pub fn parse_program<'src>(tokens: Vec<Spanned<Token<'src>>>, source: &'src str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+	Parser::new_spanned(tokens, source).parse()
+}
+
+/// Tokenizes and parses `source`.  If scanning fails the scan errors are returned without parsing.
+pub fn parse<S: AsRef<str> + ?Sized>(source: &S) -> Result<Vec<Stmt>, Vec<SyntaxError>> {
+	let source = source.as_ref();
+	let tokens = tokenize_spanned(source).map_err(|errors| errors.into_iter().map(SyntaxError::Lex).collect::<Vec<_>>())?;
+	Parser::new_spanned(tokens, source).parse().map_err(|errors| errors.into_iter().map(SyntaxError::Parse).collect())
+}
+
+/// Renders `tokens` as JSON, see the `json` module for the format
+pub fn tokens_to_json(tokens: &[Token]) -> String {
+	json::tokens_to_json(tokens)
 }
\ No newline at end of file
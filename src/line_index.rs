@@ -0,0 +1,142 @@
+use std::ops::Range;
+
+/// Maps byte offsets in a source to lines and columns.  Line starts are found once up front, so each lookup
+/// is a binary search rather than a walk over the source.
+///
+/// Lines and columns are 1-based, and columns count characters rather than bytes.  A `\n` belongs to the
+/// line it ends.
+pub struct LineIndex<'src> {
+	source: &'src str,
+	// Byte offset of the first character of every line, so this is never empty
+	line_starts: Vec<usize>
+}
+
+impl<'src> LineIndex<'src> {
+	pub fn new(source: &'src str) -> LineIndex<'src> {
+		let line_starts = std::iter::once(0)
+			.chain(source.match_indices('\n').map(|(offset, _)| offset + 1))
+			.collect();
+
+		LineIndex {
+			source,
+			line_starts
+		}
+	}
+
+	pub fn source(&self) -> &'src str {
+		self.source
+	}
+
+	/// How many lines the source has.  An empty source, or one ending in `\n`, still has a final empty line.
+	pub fn line_count(&self) -> usize {
+		self.line_starts.len()
+	}
+
+	/// Byte offset at which each line starts, in order.  The first is always `0` and the rest are just after
+	/// each `\n`.
+	pub fn line_starts(&self) -> &[usize] {
+		&self.line_starts
+	}
+
+	/// The (line, column) of `offset`, which may be anywhere from `0` up to and including the source length.
+	/// Offsets past the end are treated as the end, and ones inside a multi-byte character as the start of it.
+	pub fn line_col(&self, offset: usize) -> (usize, usize) {
+		let mut offset = offset.min(self.source.len());
+		while !self.source.is_char_boundary(offset) {
+			offset -= 1;
+		}
+		let line = self.line_starts.partition_point(|&start| start <= offset);
+		let column = self.source[self.line_starts[line - 1]..offset].chars().count() + 1;
+		(line, column)
+	}
+
+	/// Byte range of `line`, not including its line ending (`\n` or `\r\n`).  Panics if there is no such line.
+	pub fn line_span(&self, line: usize) -> Range<usize> {
+		let start = self.line_starts[line - 1];
+		let mut end = match self.line_starts.get(line) {
+			Some(&next_start) => next_start - 1,
+			None => self.source.len()
+		};
+		if self.source[start..end].ends_with('\r') {
+			end -= 1;
+		}
+		start..end
+	}
+
+	/// The text of `line`, not including its line ending.  Panics if there is no such line.
+	pub fn line_text(&self, line: usize) -> &'src str {
+		&self.source[self.line_span(line)]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_empty_source() {
+		let index = LineIndex::new("");
+		assert_eq!(index.line_count(), 1);
+		assert_eq!(index.line_col(0), (1, 1));
+		assert_eq!(index.line_span(1), 0..0);
+		assert_eq!(index.line_text(1), "");
+	}
+
+	#[test]
+	fn test_no_trailing_newline() {
+		let source = "var a;\nprint a;";
+		let index = LineIndex::new(source);
+		assert_eq!(index.line_count(), 2);
+		assert_eq!(index.line_col(7), (2, 1));
+		assert_eq!(index.line_col(source.len()), (2, 9));
+		assert_eq!(index.line_text(1), "var a;");
+		assert_eq!(index.line_text(2), "print a;");
+		assert_eq!(index.line_span(2), 7..15);
+	}
+
+	#[test]
+	fn test_crlf() {
+		let index = LineIndex::new("a;\r\nb;\r\n");
+		assert_eq!(index.line_count(), 3);
+		assert_eq!(index.line_col(4), (2, 1));
+		assert_eq!(index.line_span(1), 0..2);
+		assert_eq!(index.line_text(2), "b;");
+		assert_eq!(index.line_text(3), "");
+	}
+
+	#[test]
+	fn test_offset_on_newline() {
+		let index = LineIndex::new("ab\n\ncd");
+		assert_eq!(index.line_col(2), (1, 3));
+		assert_eq!(index.line_col(3), (2, 1));
+		assert_eq!(index.line_col(4), (3, 1));
+		assert_eq!(index.line_text(2), "");
+	}
+
+	#[test]
+	fn test_line_starts() {
+		let source = "var a;\n\nprint a;\n";
+		let index = LineIndex::new(source);
+		assert_eq!(index.line_starts(), &[0, 7, 8, 17]);
+		for &start in &index.line_starts()[1..] {
+			assert_eq!(source.as_bytes()[start - 1], b'\n');
+		}
+		assert_eq!(LineIndex::new("").line_starts(), &[0]);
+	}
+
+	#[test]
+	fn test_unicode_columns() {
+		let index = LineIndex::new("\"héllo\" @");
+		assert_eq!(index.line_col(9), (1, 9));
+	}
+
+	#[test]
+	fn test_offsets_out_of_range() {
+		let index = LineIndex::new("ab\né");
+		assert_eq!(index.line_col(5), (2, 2));
+		assert_eq!(index.line_col(usize::MAX), (2, 2));
+		// Inside the two bytes of 'é'
+		assert_eq!(index.line_col(4), (2, 1));
+		assert_eq!(crate::tokenizer::get_2d_location("ab", 5), (1, 3));
+	}
+}
\ No newline at end of file
@@ -0,0 +1,174 @@
+//! A compact binary encoding for token streams.
+//!
+//! Each token is written as its `TokenKind` byte.  Identifiers and strings follow it with a little-endian
+//! `u32` byte length and their UTF-8 contents, numbers with the 8 little-endian bytes of the `f64`.
+
+use std::borrow::Cow;
+
+use crate::tokenizer::{Token, TokenKind};
+
+#[derive(Debug, PartialEq)]
+pub enum UnpackError {
+	UnknownKind {
+		byte: u8,
+		offset: usize
+	},
+	UnexpectedEnd {
+		offset: usize
+	},
+	InvalidUtf8 {
+		offset: usize
+	}
+}
+
+impl std::fmt::Display for UnpackError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			UnpackError::UnknownKind { byte, offset } => write!(f, "Unknown token kind {} at byte {}", byte, offset),
+			UnpackError::UnexpectedEnd { offset } => write!(f, "Unexpected end of data at byte {}", offset),
+			UnpackError::InvalidUtf8 { offset } => write!(f, "Invalid UTF-8 in payload at byte {}", offset)
+		}
+	}
+}
+
+impl std::error::Error for UnpackError {}
+
+/// # Panics
+///
+/// If a text payload is longer than `u32::MAX` bytes.  Scanned tokens never are, since sources that large are
+/// rejected, but tokens built by hand could be.
+pub fn pack_tokens(tokens: &[Token]) -> Vec<u8> {
+	let mut bytes = Vec::with_capacity(tokens.len());
+
+	for token in tokens {
+		bytes.push(token.kind() as u8);
+
+		match token {
+			Token::Identifier(text) | Token::String(text) | Token::Comment(text) | Token::Whitespace(text) => {
+				let len = u32::try_from(text.len()).expect("Token text is too long to pack, the limit is u32::MAX bytes");
+				bytes.extend_from_slice(&len.to_le_bytes());
+				bytes.extend_from_slice(text.as_bytes());
+			},
+			Token::Number(num) => bytes.extend_from_slice(&num.to_le_bytes()),
+			_ => {}
+		}
+	}
+
+	bytes
+}
+
+/// Decodes a packed stream.  Text payloads borrow from `bytes`.
+pub fn unpack_tokens(bytes: &[u8]) -> Result<Vec<Token<'_>>, UnpackError> {
+	let mut tokens = vec![];
+	let mut offset = 0;
+
+	while offset < bytes.len() {
+		let kind = TokenKind::from_u8(bytes[offset]).ok_or(UnpackError::UnknownKind {
+			byte: bytes[offset],
+			offset
+		})?;
+		offset += 1;
+
+		let token = match kind {
+			TokenKind::Identifier | TokenKind::String | TokenKind::Comment | TokenKind::Whitespace => {
+				let len = u32::from_le_bytes(take(bytes, &mut offset)?) as usize;
+				let text_offset = offset;
+				// A corrupt length could overflow `usize` on 32-bit targets
+				let text = offset.checked_add(len)
+					.and_then(|end| bytes.get(offset..end))
+					.ok_or(UnpackError::UnexpectedEnd { offset: bytes.len() })?;
+				offset += len;
+
+				let text = Cow::Borrowed(
+					std::str::from_utf8(text).map_err(|_| UnpackError::InvalidUtf8 { offset: text_offset })?
+				);
+
+				match kind {
+					TokenKind::Identifier => Token::Identifier(text),
+					TokenKind::String => Token::String(text),
+					TokenKind::Comment => Token::Comment(text),
+					_ => Token::Whitespace(text)
+				}
+			},
+			TokenKind::Number => Token::Number(f64::from_le_bytes(take(bytes, &mut offset)?)),
+			kind => kind.fixed_token().unwrap()
+		};
+
+		tokens.push(token);
+	}
+
+	Ok(tokens)
+}
+
+fn take<const N: usize>(bytes: &[u8], offset: &mut usize) -> Result<[u8; N], UnpackError> {
+	let chunk = offset.checked_add(N)
+		.and_then(|end| bytes.get(*offset..end))
+		.ok_or(UnpackError::UnexpectedEnd { offset: bytes.len() })?;
+	*offset += N;
+	Ok(chunk.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tokenizer::{Tokenizer, TokenizerOptions};
+
+	#[test]
+	fn test_round_trip() {
+		let source = r#"
+		var greeting = "Hello, wörld 😀";
+		var ratio = 0.1;
+		while (ratio <= 10) {
+			print greeting + "!";
+			ratio = ratio * -2.5;
+		}
+		"#;
+		let tokens = Tokenizer::new(source).tokenize().unwrap();
+		let packed = pack_tokens(&tokens);
+		assert_eq!(unpack_tokens(&packed).unwrap(), tokens);
+	}
+
+	#[test]
+	fn test_round_trip_trivia() {
+		let options = TokenizerOptions {
+			trivia: true,
+			..Default::default()
+		};
+		let tokens = Tokenizer::new_with_options("// hi
+print 1; /* done */", options).tokenize().unwrap();
+		let packed = pack_tokens(&tokens);
+		assert_eq!(unpack_tokens(&packed).unwrap(), tokens);
+	}
+
+	#[test]
+	fn test_encoding() {
+		let tokens = vec![
+			Token::Print,
+			Token::String(Cow::from("hi")),
+			Token::Number(1.0),
+			Token::Eof
+		];
+		let mut expected = vec![TokenKind::Print as u8, TokenKind::String as u8, 2, 0, 0, 0, b'h', b'i', TokenKind::Number as u8];
+		expected.extend_from_slice(&1.0f64.to_le_bytes());
+		expected.push(TokenKind::Eof as u8);
+		assert_eq!(pack_tokens(&tokens), expected);
+	}
+
+	#[test]
+	fn test_malformed() {
+		assert_eq!(unpack_tokens(&[255]), Err(UnpackError::UnknownKind { byte: 255, offset: 0 }));
+		assert_eq!(unpack_tokens(&[TokenKind::Number as u8, 0, 0]), Err(UnpackError::UnexpectedEnd { offset: 3 }));
+		assert_eq!(
+			unpack_tokens(&[TokenKind::String as u8, 5, 0, 0, 0, b'a']),
+			Err(UnpackError::UnexpectedEnd { offset: 6 })
+		);
+		assert_eq!(
+			unpack_tokens(&[TokenKind::Identifier as u8, 1, 0, 0, 0, 0xFF]),
+			Err(UnpackError::InvalidUtf8 { offset: 5 })
+		);
+		assert_eq!(
+			unpack_tokens(&[TokenKind::String as u8, 0xFF, 0xFF, 0xFF, 0xFF, b'a']),
+			Err(UnpackError::UnexpectedEnd { offset: 6 })
+		);
+	}
+}
\ No newline at end of file
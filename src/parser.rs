@@ -0,0 +1,1038 @@
+use crate::ast::{BinaryOp, Expr, Literal, Stmt, UnaryOp};
+use crate::line_index::LineIndex;
+use crate::tokenizer::{LexError, Location, Span, Spanned, Token, TokenKind};
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+	/// `expected` describes what should have come next, like `"';' after value"` or `"expression"`
+	Unexpected {
+		expected: String,
+		found: Token<'static>,
+		location: Option<Location>
+	},
+	/// `location` points at the `=`
+	InvalidAssignmentTarget {
+		location: Option<Location>
+	}
+}
+
+impl ParseError {
+	/// Only known when the parser was given spanned tokens
+	pub fn location(&self) -> Option<Location> {
+		match self {
+			ParseError::Unexpected { location, .. } => *location,
+			ParseError::InvalidAssignmentTarget { location } => *location
+		}
+	}
+
+	pub fn message(&self) -> String {
+		match self {
+			ParseError::Unexpected { expected, found, .. } => format!("Expected {}, found {}", expected, describe(found)),
+			ParseError::InvalidAssignmentTarget { .. } => String::from("Invalid assignment target")
+		}
+	}
+}
+
+impl std::fmt::Display for ParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.location() {
+			Some(location) => write!(f, "[{}:{}] Error: {}", location.line, location.column, self.message()),
+			None => write!(f, "Error: {}", self.message())
+		}
+	}
+}
+
+impl std::error::Error for ParseError {}
+
+/// Anything that stops a source from being parsed, see `lox_rs::parse`
+#[derive(Debug, PartialEq)]
+pub enum SyntaxError {
+	Lex(LexError),
+	Parse(ParseError)
+}
+
+impl SyntaxError {
+	pub fn location(&self) -> Option<Location> {
+		match self {
+			SyntaxError::Lex(e) => e.location(),
+			SyntaxError::Parse(e) => e.location()
+		}
+	}
+
+	pub fn message(&self) -> String {
+		match self {
+			SyntaxError::Lex(e) => e.message(),
+			SyntaxError::Parse(e) => e.message()
+		}
+	}
+}
+
+impl std::fmt::Display for SyntaxError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			SyntaxError::Lex(e) => write!(f, "{}", e),
+			SyntaxError::Parse(e) => write!(f, "{}", e)
+		}
+	}
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Everything parsed from a program: the statements that parsed cleanly and an error for each one that didn't
+#[derive(Debug, Default, PartialEq)]
+pub struct ParseOutput {
+	pub statements: Vec<Stmt>,
+	pub errors: Vec<ParseError>
+}
+
+/// Names the token an error was found at
+fn describe(token: &Token) -> String {
+	match token {
+		Token::Eof => String::from("end of input"),
+		token => format!("'{}'", token)
+	}
+}
+
+/// A recursive-descent parser over a token list, following the usual Lox precedence from loosest to
+/// tightest: assignment, equality, comparison, term, factor, unary, indexing, primary.
+pub struct Parser<'src> {
+	tokens: Vec<Token<'src>>,
+	// Parallel to `tokens`, and only filled in along with `line_index` when the tokens came with spans
+	spans: Vec<Span>,
+	line_index: Option<LineIndex<'src>>,
+	current: usize,
+	errors: Vec<ParseError>,
+	// How many blocks the statement being parsed is inside of, so recovery knows whether a `}` ends one
+	block_depth: usize
+}
+
+impl<'src> Parser<'src> {
+	/// Errors from a parser made this way have no location, see `new_spanned`
+	pub fn new(mut tokens: Vec<Token<'src>>) -> Parser<'src> {
+		tokens.retain(|token| !token.is_trivia());
+		Parser::from_parts(tokens, Vec::new(), None)
+	}
+
+	/// Errors carry the location they were found at in `source`
+	pub fn new_spanned(tokens: Vec<Spanned<Token<'src>>>, source: &'src str) -> Parser<'src> {
+		let (tokens, spans) = tokens.into_iter()
+			.filter(|token| !token.value.is_trivia())
+			.map(|token| (token.value, token.span))
+			.unzip();
+		Parser::from_parts(tokens, spans, Some(LineIndex::new(source)))
+	}
+
+	fn from_parts(mut tokens: Vec<Token<'src>>, mut spans: Vec<Span>, line_index: Option<LineIndex<'src>>) -> Parser<'src> {
+		// Lets the parser always have something to peek at, even for a list that wasn't straight from a tokenizer
+		if tokens.last() != Some(&Token::Eof) {
+			tokens.push(Token::Eof);
+			if line_index.is_some() {
+				let end = spans.last().map_or(0, |span| span.end);
+				spans.push(Span {
+					start: end,
+					end
+				});
+			}
+		}
+
+		Parser {
+			tokens,
+			spans,
+			line_index,
+			current: 0,
+			errors: Vec::new(),
+			block_depth: 0
+		}
+	}
+
+	/// Parses a whole program, returning every error found if there are any (see `parse_lossy`)
+	pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
+		let output = self.parse_lossy();
+		if !output.errors.is_empty() {
+			return Err(output.errors);
+		}
+		Ok(output.statements)
+	}
+
+	/// Parses a whole program, keeping every statement that could be parsed even when there are errors.  After
+	/// an error the rest of the statement is skipped and parsing carries on from the next one.
+	pub fn parse_lossy(&mut self) -> ParseOutput {
+		self.current = 0;
+		self.errors.clear();
+		self.block_depth = 0;
+
+		let mut statements = Vec::new();
+		while *self.peek() != Token::Eof {
+			statements.extend(self.recovering_declaration());
+		}
+
+		ParseOutput {
+			statements,
+			errors: std::mem::take(&mut self.errors)
+		}
+	}
+
+	/// Parses a single expression, which must make up all of the tokens
+	pub fn parse_expression(&mut self) -> Result<Expr, ParseError> {
+		let expr = self.expression()?;
+		if *self.peek() != Token::Eof {
+			return Err(self.unexpected("end of expression", false));
+		}
+		Ok(expr)
+	}
+
+	/// The location of the current token, or of the end of the previous one for things that were expected to
+	/// follow it (so a missing `;` is reported on the line it's missing from)
+	fn location(&self, after_previous: bool) -> Option<Location> {
+		let line_index = self.line_index.as_ref()?;
+
+		let offset = if after_previous && self.current > 0 {
+			self.spans[self.current - 1].end
+		} else {
+			self.spans[self.current].start
+		} as usize;
+		let (line, column) = line_index.line_col(offset);
+		Some(Location {
+			offset,
+			line,
+			column
+		})
+	}
+
+	fn unexpected(&self, expected: &str, after_previous: bool) -> ParseError {
+		ParseError::Unexpected {
+			expected: expected.to_string(),
+			found: self.peek().clone().into_owned(),
+			location: self.location(after_previous)
+		}
+	}
+
+	fn peek(&self) -> &Token<'src> {
+		&self.tokens[self.current]
+	}
+
+	/// Moves past the current token and returns it.  `Eof` is never moved past.
+	fn advance(&mut self) -> &Token<'src> {
+		let index = self.current;
+		if *self.peek() != Token::Eof {
+			self.current += 1;
+		}
+		&self.tokens[index]
+	}
+
+	fn consume(&mut self, kind: TokenKind, expected: &str) -> Result<(), ParseError> {
+		if self.peek().kind() != kind {
+			return Err(self.unexpected(expected, true));
+		}
+		self.advance();
+		Ok(())
+	}
+
+	fn consume_identifier(&mut self, expected: &str) -> Result<String, ParseError> {
+		if let Token::Identifier(name) = self.peek() {
+			let name = name.to_string();
+			self.advance();
+			return Ok(name);
+		}
+		Err(self.unexpected(expected, false))
+	}
+
+	/// A declaration, or `None` if it had an error, which gets recorded before skipping to the next statement
+	fn recovering_declaration(&mut self) -> Option<Stmt> {
+		let start = self.current;
+		match self.declaration() {
+			Ok(stmt) => Some(stmt),
+			Err(e) => {
+				self.errors.push(e);
+				// A declaration that failed on its first token has to skip it, or parsing would never move on
+				if self.current == start {
+					self.advance();
+				}
+				self.synchronize();
+				None
+			}
+		}
+	}
+
+	/// Discards tokens until the likely start of the next statement: just after a `;`, at a keyword that begins
+	/// one, or at the `}` closing the block being parsed.  The current token is kept if it's already there, so
+	/// the statement after a missing `;` still gets parsed.
+	fn synchronize(&mut self) {
+		while *self.peek() != Token::Eof {
+			if self.current > 0 && self.tokens[self.current - 1] == Token::Semicolon {
+				return;
+			}
+			match self.peek() {
+				Token::Class | Token::Fun | Token::Var | Token::For | Token::If | Token::While | Token::Do | Token::Print | Token::Assert | Token::Return => return,
+				Token::RightBrace if self.block_depth > 0 => return,
+				_ => {
+					self.advance();
+				}
+			}
+		}
+	}
+
+	fn declaration(&mut self) -> Result<Stmt, ParseError> {
+		if *self.peek() == Token::Var {
+			self.advance();
+			return self.var_declaration();
+		}
+		self.statement()
+	}
+
+	fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
+		let name = self.consume_identifier("variable name")?;
+		let initializer = if *self.peek() == Token::Equal {
+			self.advance();
+			Some(self.expression()?)
+		} else {
+			None
+		};
+		self.consume(TokenKind::Semicolon, "';' after variable declaration")?;
+
+		Ok(Stmt::Var {
+			name,
+			initializer
+		})
+	}
+
+	fn statement(&mut self) -> Result<Stmt, ParseError> {
+		match self.peek() {
+			Token::Print => {
+				self.advance();
+				let value = self.expression()?;
+				self.consume(TokenKind::Semicolon, "';' after value")?;
+				Ok(Stmt::Print(value))
+			},
+			Token::Assert => {
+				let line = self.location(false).map(|location| location.line);
+				self.advance();
+				let condition = self.expression()?;
+				self.consume(TokenKind::Semicolon, "';' after assertion")?;
+				Ok(Stmt::Assert {
+					condition,
+					line
+				})
+			},
+			Token::LeftBrace if !self.at_map_literal() => {
+				self.advance();
+				Ok(Stmt::Block(self.block()?))
+			},
+			Token::If => {
+				self.advance();
+				self.if_statement()
+			},
+			Token::While => {
+				self.advance();
+				self.consume(TokenKind::LeftParen, "'(' after 'while'")?;
+				let condition = self.expression()?;
+				self.consume(TokenKind::RightParen, "')' after condition")?;
+				Ok(Stmt::While {
+					condition,
+					body: Box::new(self.statement()?)
+				})
+			},
+			Token::Do => {
+				self.advance();
+				let body = Box::new(self.statement()?);
+				self.consume(TokenKind::While, "'while' after do-while body")?;
+				self.consume(TokenKind::LeftParen, "'(' after 'while'")?;
+				let condition = self.expression()?;
+				self.consume(TokenKind::RightParen, "')' after condition")?;
+				self.consume(TokenKind::Semicolon, "';' after do-while loop")?;
+				Ok(Stmt::DoWhile {
+					body,
+					condition
+				})
+			},
+			Token::For => {
+				self.advance();
+				self.for_statement()
+			},
+			_ => {
+				let expr = self.expression()?;
+				self.consume(TokenKind::Semicolon, "';' after expression")?;
+				Ok(Stmt::Expression(expr))
+			}
+		}
+	}
+
+	/// Whether the `{` at the start of a statement opens a map rather than a block, i.e. it's followed by a
+	/// single token key and `:`.  Maps with any other kind of key have to be wrapped in parentheses there.
+	fn at_map_literal(&self) -> bool {
+		matches!(self.tokens.get(self.current + 2), Some(Token::Colon))
+	}
+
+	/// The statements of a block whose `{` has already been consumed
+	fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+		let mut statements = Vec::new();
+		self.block_depth += 1;
+		while !matches!(self.peek(), Token::RightBrace | Token::Eof) {
+			statements.extend(self.recovering_declaration());
+		}
+		self.block_depth -= 1;
+		self.consume(TokenKind::RightBrace, "'}' after block")?;
+		Ok(statements)
+	}
+
+	fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+		self.consume(TokenKind::LeftParen, "'(' after 'if'")?;
+		let condition = self.expression()?;
+		self.consume(TokenKind::RightParen, "')' after if condition")?;
+
+		let then_branch = Box::new(self.statement()?);
+		let else_branch = if *self.peek() == Token::Else {
+			self.advance();
+			Some(Box::new(self.statement()?))
+		} else {
+			None
+		};
+
+		Ok(Stmt::If {
+			condition,
+			then_branch,
+			else_branch
+		})
+	}
+
+	/// `for (init; condition; increment) body` becomes `{ init; while (condition) { body; increment; } }`
+	fn for_statement(&mut self) -> Result<Stmt, ParseError> {
+		self.consume(TokenKind::LeftParen, "'(' after 'for'")?;
+
+		let initializer = match self.peek() {
+			Token::Semicolon => {
+				self.advance();
+				None
+			},
+			Token::Var => {
+				self.advance();
+				Some(self.var_declaration()?)
+			},
+			_ => {
+				let expr = self.expression()?;
+				self.consume(TokenKind::Semicolon, "';' after loop initializer")?;
+				Some(Stmt::Expression(expr))
+			}
+		};
+
+		let condition = if *self.peek() == Token::Semicolon {
+			Expr::Literal(Literal::Bool(true))
+		} else {
+			self.expression()?
+		};
+		self.consume(TokenKind::Semicolon, "';' after loop condition")?;
+
+		let increment = if *self.peek() == Token::RightParen {
+			None
+		} else {
+			Some(self.expression()?)
+		};
+		self.consume(TokenKind::RightParen, "')' after for clauses")?;
+
+		let mut body = self.statement()?;
+		if let Some(increment) = increment {
+			body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
+		}
+		body = Stmt::While {
+			condition,
+			body: Box::new(body)
+		};
+		if let Some(initializer) = initializer {
+			body = Stmt::Block(vec![initializer, body]);
+		}
+
+		Ok(body)
+	}
+
+	fn expression(&mut self) -> Result<Expr, ParseError> {
+		self.assignment()
+	}
+
+	fn assignment(&mut self) -> Result<Expr, ParseError> {
+		let expr = self.equality()?;
+
+		if *self.peek() == Token::Equal {
+			let Expr::Variable(name) = expr else {
+				return Err(ParseError::InvalidAssignmentTarget {
+					location: self.location(false)
+				});
+			};
+			self.advance();
+			return Ok(Expr::Assign {
+				name,
+				value: Box::new(self.assignment()?)
+			});
+		}
+
+		Ok(expr)
+	}
+
+	/// Parses a left-associative run of `operand`s separated by whichever operators `op_for` recognizes
+	fn binary(&mut self, operand: fn(&mut Self) -> Result<Expr, ParseError>, op_for: fn(&Token) -> Option<BinaryOp>) -> Result<Expr, ParseError> {
+		let mut expr = operand(self)?;
+
+		while let Some(op) = op_for(self.peek()) {
+			self.advance();
+			let right = operand(self)?;
+			expr = Expr::Binary {
+				left: Box::new(expr),
+				op,
+				right: Box::new(right)
+			};
+		}
+
+		Ok(expr)
+	}
+
+	fn equality(&mut self) -> Result<Expr, ParseError> {
+		self.binary(Self::comparison, |token| match token {
+			Token::EqualEqual => Some(BinaryOp::Equal),
+			Token::BangEqual => Some(BinaryOp::NotEqual),
+			_ => None
+		})
+	}
+
+	fn comparison(&mut self) -> Result<Expr, ParseError> {
+		self.binary(Self::term, |token| match token {
+			Token::Greater => Some(BinaryOp::Greater),
+			Token::GreatEqual => Some(BinaryOp::GreaterEqual),
+			Token::Less => Some(BinaryOp::Less),
+			Token::LessEqual => Some(BinaryOp::LessEqual),
+			_ => None
+		})
+	}
+
+	fn term(&mut self) -> Result<Expr, ParseError> {
+		self.binary(Self::factor, |token| match token {
+			Token::Plus => Some(BinaryOp::Add),
+			Token::Minus => Some(BinaryOp::Subtract),
+			_ => None
+		})
+	}
+
+	fn factor(&mut self) -> Result<Expr, ParseError> {
+		self.binary(Self::unary, |token| match token {
+			Token::Star => Some(BinaryOp::Multiply),
+			Token::Slash => Some(BinaryOp::Divide),
+			_ => None
+		})
+	}
+
+	fn unary(&mut self) -> Result<Expr, ParseError> {
+		let op = match self.peek() {
+			Token::Minus => UnaryOp::Negate,
+			Token::Bang => UnaryOp::Not,
+			_ => return self.index()
+		};
+		self.advance();
+
+		Ok(Expr::Unary {
+			op,
+			right: Box::new(self.unary()?)
+		})
+	}
+
+	fn index(&mut self) -> Result<Expr, ParseError> {
+		let mut expr = self.primary()?;
+
+		while *self.peek() == Token::LeftBracket {
+			self.advance();
+			let index = self.expression()?;
+			self.consume(TokenKind::RightBracket, "']' after index")?;
+			expr = Expr::Index {
+				object: Box::new(expr),
+				index: Box::new(index)
+			};
+		}
+
+		Ok(expr)
+	}
+
+	/// The elements of a list literal whose `[` has already been consumed.  A trailing comma is allowed.
+	fn list(&mut self) -> Result<Vec<Expr>, ParseError> {
+		let mut elements = Vec::new();
+
+		while *self.peek() != Token::RightBracket {
+			elements.push(self.expression()?);
+			if *self.peek() != Token::Comma {
+				break;
+			}
+			self.advance();
+		}
+		self.consume(TokenKind::RightBracket, "']' after list elements")?;
+
+		Ok(elements)
+	}
+
+	/// The entries of a map literal whose `{` has already been consumed.  A trailing comma is allowed.
+	fn map(&mut self) -> Result<Vec<(Expr, Expr)>, ParseError> {
+		let mut entries = Vec::new();
+
+		while *self.peek() != Token::RightBrace {
+			let key = self.expression()?;
+			self.consume(TokenKind::Colon, "':' after map key")?;
+			entries.push((key, self.expression()?));
+			if *self.peek() != Token::Comma {
+				break;
+			}
+			self.advance();
+		}
+		self.consume(TokenKind::RightBrace, "'}' after map entries")?;
+
+		Ok(entries)
+	}
+
+	fn primary(&mut self) -> Result<Expr, ParseError> {
+		let expr = match self.peek() {
+			Token::Number(num) => Expr::Literal(Literal::Number(*num)),
+			Token::String(value) => Expr::Literal(Literal::String(value.to_string())),
+			Token::True => Expr::Literal(Literal::Bool(true)),
+			Token::False => Expr::Literal(Literal::Bool(false)),
+			Token::Nil => Expr::Literal(Literal::Nil),
+			Token::Identifier(name) => Expr::Variable(name.to_string()),
+			Token::LeftParen => {
+				self.advance();
+				let expr = self.expression()?;
+				self.consume(TokenKind::RightParen, "')' after expression")?;
+				return Ok(Expr::Grouping(Box::new(expr)));
+			},
+			Token::LeftBracket => {
+				self.advance();
+				return Ok(Expr::List(self.list()?));
+			},
+			Token::LeftBrace => {
+				self.advance();
+				return Ok(Expr::Map(self.map()?));
+			},
+			_ => return Err(self.unexpected("expression", false))
+		};
+		self.advance();
+
+		Ok(expr)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{tokenize, tokenize_spanned};
+
+	fn parse(source: &str) -> Result<Expr, ParseError> {
+		Parser::new(tokenize(source).unwrap()).parse_expression()
+	}
+
+	fn number(num: f64) -> Box<Expr> {
+		Box::new(Expr::Literal(Literal::Number(num)))
+	}
+
+	#[test]
+	fn test_precedence() {
+		assert_eq!(parse("1 + 2 * 3"), Ok(Expr::Binary {
+			left: number(1.0),
+			op: BinaryOp::Add,
+			right: Box::new(Expr::Binary {
+				left: number(2.0),
+				op: BinaryOp::Multiply,
+				right: number(3.0)
+			})
+		}));
+	}
+
+	#[test]
+	fn test_left_associativity() {
+		assert_eq!(parse("1 - 2 - 3"), Ok(Expr::Binary {
+			left: Box::new(Expr::Binary {
+				left: number(1.0),
+				op: BinaryOp::Subtract,
+				right: number(2.0)
+			}),
+			op: BinaryOp::Subtract,
+			right: number(3.0)
+		}));
+	}
+
+	#[test]
+	fn test_grouping_and_unary() {
+		assert_eq!(parse("-(1 + 2) == !true"), Ok(Expr::Binary {
+			left: Box::new(Expr::Unary {
+				op: UnaryOp::Negate,
+				right: Box::new(Expr::Grouping(Box::new(Expr::Binary {
+					left: number(1.0),
+					op: BinaryOp::Add,
+					right: number(2.0)
+				})))
+			}),
+			op: BinaryOp::Equal,
+			right: Box::new(Expr::Unary {
+				op: UnaryOp::Not,
+				right: Box::new(Expr::Literal(Literal::Bool(true)))
+			})
+		}));
+		assert_eq!(parse("\"a\" < nil"), Ok(Expr::Binary {
+			left: Box::new(Expr::Literal(Literal::String(String::from("a")))),
+			op: BinaryOp::Less,
+			right: Box::new(Expr::Literal(Literal::Nil))
+		}));
+	}
+
+	#[test]
+	fn test_unary_chain() {
+		assert_eq!(parse("!!true"), Ok(Expr::Unary {
+			op: UnaryOp::Not,
+			right: Box::new(Expr::Unary {
+				op: UnaryOp::Not,
+				right: Box::new(Expr::Literal(Literal::Bool(true)))
+			})
+		}));
+	}
+
+	#[test]
+	fn test_errors() {
+		assert_eq!(parse("(1 + 2"), Err(ParseError::Unexpected {
+			expected: String::from("')' after expression"),
+			found: Token::Eof,
+			location: None
+		}));
+		assert_eq!(parse("(1 + 2").unwrap_err().to_string(), "Error: Expected ')' after expression, found end of input");
+		assert_eq!(parse("1 +").unwrap_err().message(), "Expected expression, found end of input");
+		assert_eq!(parse("1 + ;").unwrap_err().message(), "Expected expression, found ';'");
+		assert_eq!(parse("1 2").unwrap_err().message(), "Expected end of expression, found '2'");
+	}
+
+	fn parse_program(source: &str) -> Result<Vec<Stmt>, Vec<String>> {
+		crate::parse_program(crate::tokenize_spanned(source).unwrap(), source)
+			.map_err(|errors| errors.iter().map(ToString::to_string).collect())
+	}
+
+	fn variable(name: &str) -> Box<Expr> {
+		Box::new(Expr::Variable(String::from(name)))
+	}
+
+	#[test]
+	fn test_program() {
+		let source = r#"
+		var i = 0;
+		while (i < 3) {
+			print i;
+			i = i + 1;
+		}
+		if (i == 3) print "done"; else print "oops";
+		"#;
+		assert_eq!(parse_program(source), Ok(vec![
+			Stmt::Var {
+				name: String::from("i"),
+				initializer: Some(Expr::Literal(Literal::Number(0.0)))
+			},
+			Stmt::While {
+				condition: Expr::Binary {
+					left: variable("i"),
+					op: BinaryOp::Less,
+					right: number(3.0)
+				},
+				body: Box::new(Stmt::Block(vec![
+					Stmt::Print(Expr::Variable(String::from("i"))),
+					Stmt::Expression(Expr::Assign {
+						name: String::from("i"),
+						value: Box::new(Expr::Binary {
+							left: variable("i"),
+							op: BinaryOp::Add,
+							right: number(1.0)
+						})
+					})
+				]))
+			},
+			Stmt::If {
+				condition: Expr::Binary {
+					left: variable("i"),
+					op: BinaryOp::Equal,
+					right: number(3.0)
+				},
+				then_branch: Box::new(Stmt::Print(Expr::Literal(Literal::String(String::from("done"))))),
+				else_branch: Some(Box::new(Stmt::Print(Expr::Literal(Literal::String(String::from("oops"))))))
+			}
+		]));
+	}
+
+	#[test]
+	fn test_assert() {
+		assert_eq!(parse_program("assert 1 == 1;"), Ok(vec![Stmt::Assert {
+			condition: Expr::Binary {
+				left: number(1.0),
+				op: BinaryOp::Equal,
+				right: number(1.0)
+			},
+			line: Some(1)
+		}]));
+		assert_eq!(parse_program("print 1;\n\tassert assertion;"), Ok(vec![
+			Stmt::Print(Expr::Literal(Literal::Number(1.0))),
+			Stmt::Assert {
+				condition: Expr::Variable(String::from("assertion")),
+				line: Some(2)
+			}
+		]));
+		assert_eq!(Parser::new(tokenize("assert true;").unwrap()).parse(), Ok(vec![Stmt::Assert {
+			condition: Expr::Literal(Literal::Bool(true)),
+			line: None
+		}]));
+		assert_eq!(parse_program("assert ok\nassert ;"), Err(vec![
+			String::from("[1:10] Error: Expected ';' after assertion, found 'assert'"),
+			String::from("[2:8] Error: Expected expression, found ';'")
+		]));
+	}
+
+	#[test]
+	fn test_do_while() {
+		assert_eq!(parse_program("do { print 1; } while (false);"), Ok(vec![Stmt::DoWhile {
+			body: Box::new(Stmt::Block(vec![Stmt::Print(Expr::Literal(Literal::Number(1.0)))])),
+			condition: Expr::Literal(Literal::Bool(false))
+		}]));
+		assert_eq!(parse_program("do done = double; while (!done);"), Ok(vec![Stmt::DoWhile {
+			body: Box::new(Stmt::Expression(Expr::Assign {
+				name: String::from("done"),
+				value: variable("double")
+			})),
+			condition: Expr::Unary {
+				op: UnaryOp::Not,
+				right: variable("done")
+			}
+		}]));
+		assert_eq!(parse_program("do print 1; (false);"), Err(vec![
+			String::from("[1:12] Error: Expected 'while' after do-while body, found '('")
+		]));
+		assert_eq!(parse_program("do print 1; while (false)\nprint 2;"), Err(vec![
+			String::from("[1:26] Error: Expected ';' after do-while loop, found 'print'")
+		]));
+	}
+
+	#[test]
+	fn test_for_desugaring() {
+		assert_eq!(parse_program("for (var i = 0; i < 2; i = i + 1) print i;"), parse_program(r#"
+		{
+			var i = 0;
+			while (i < 2) {
+				print i;
+				i = i + 1;
+			}
+		}
+		"#));
+		assert_eq!(parse_program("for (;;) print 1;"), Ok(vec![
+			Stmt::While {
+				condition: Expr::Literal(Literal::Bool(true)),
+				body: Box::new(Stmt::Print(Expr::Literal(Literal::Number(1.0))))
+			}
+		]));
+	}
+
+	#[test]
+	fn test_program_errors() {
+		assert_eq!(parse_program("var a = 1\nprint a;"), Err(vec![
+			String::from("[1:10] Error: Expected ';' after variable declaration, found 'print'")
+		]));
+		assert_eq!(parse_program("{\n\tprint 1;\n"), Err(vec![
+			String::from("[2:10] Error: Expected '}' after block, found end of input")
+		]));
+		assert_eq!(parse_program("print 1;\n}"), Err(vec![
+			String::from("[2:1] Error: Expected expression, found '}'")
+		]));
+		assert_eq!(parse_program("1 = 2;"), Err(vec![
+			String::from("[1:3] Error: Invalid assignment target")
+		]));
+	}
+
+	#[test]
+	fn test_lists() {
+		assert_eq!(parse("[1, 2, 3]"), Ok(Expr::List(vec![
+			Expr::Literal(Literal::Number(1.0)),
+			Expr::Literal(Literal::Number(2.0)),
+			Expr::Literal(Literal::Number(3.0))
+		])));
+		assert_eq!(parse("[]"), Ok(Expr::List(vec![])));
+		assert_eq!(parse("[1,]"), Ok(Expr::List(vec![Expr::Literal(Literal::Number(1.0))])));
+		assert_eq!(parse("[1 2]").unwrap_err().message(), "Expected ']' after list elements, found '2'");
+	}
+
+	#[test]
+	fn test_index() {
+		assert_eq!(parse("-a[0][i + 1]"), Ok(Expr::Unary {
+			op: UnaryOp::Negate,
+			right: Box::new(Expr::Index {
+				object: Box::new(Expr::Index {
+					object: variable("a"),
+					index: number(0.0)
+				}),
+				index: Box::new(Expr::Binary {
+					left: variable("i"),
+					op: BinaryOp::Add,
+					right: number(1.0)
+				})
+			})
+		}));
+		assert_eq!(parse("a[0").unwrap_err().message(), "Expected ']' after index, found end of input");
+	}
+
+	#[test]
+	fn test_parse_source() {
+		let source = r#"
+		var a123 = false;
+		var x = 1;
+		var y = 2;
+		var z;
+		print a123;
+		print x + y;
+		{
+			var x = 3;
+			{ print x; }
+		}
+		"#;
+		assert_eq!(crate::parse(source), Ok(vec![
+			Stmt::Var {
+				name: String::from("a123"),
+				initializer: Some(Expr::Literal(Literal::Bool(false)))
+			},
+			Stmt::Var {
+				name: String::from("x"),
+				initializer: Some(Expr::Literal(Literal::Number(1.0)))
+			},
+			Stmt::Var {
+				name: String::from("y"),
+				initializer: Some(Expr::Literal(Literal::Number(2.0)))
+			},
+			Stmt::Var {
+				name: String::from("z"),
+				initializer: None
+			},
+			Stmt::Print(Expr::Variable(String::from("a123"))),
+			Stmt::Print(Expr::Binary {
+				left: variable("x"),
+				op: BinaryOp::Add,
+				right: variable("y")
+			}),
+			Stmt::Block(vec![
+				Stmt::Var {
+					name: String::from("x"),
+					initializer: Some(Expr::Literal(Literal::Number(3.0)))
+				},
+				Stmt::Block(vec![
+					Stmt::Print(Expr::Variable(String::from("x")))
+				])
+			])
+		]));
+	}
+
+	#[test]
+	fn test_parse_source_errors() {
+		let errors = crate::parse("print 1
+print 2;").unwrap_err();
+		assert!(matches!(&errors[..], [SyntaxError::Parse(ParseError::Unexpected { found: Token::Print, .. })]));
+		assert_eq!(errors[0].to_string(), "[1:8] Error: Expected ';' after value, found 'print'");
+
+		let errors = crate::parse("{ var a = 1;").unwrap_err();
+		assert_eq!(errors[0].message(), "Expected '}' after block, found end of input");
+
+		let errors = crate::parse("var a = @;").unwrap_err();
+		assert!(matches!(&errors[..], [SyntaxError::Lex(LexError::UnexpectedCharacter { ch: '@', .. })]));
+		assert_eq!(errors[0].location().unwrap().column, 9);
+	}
+
+	#[test]
+	fn test_maps() {
+		assert_eq!(parse("{\"a\": 1, x: [],}"), Ok(Expr::Map(vec![
+			(Expr::Literal(Literal::String(String::from("a"))), Expr::Literal(Literal::Number(1.0))),
+			(Expr::Variable(String::from("x")), Expr::List(vec![]))
+		])));
+		assert_eq!(parse("{}"), Ok(Expr::Map(vec![])));
+		assert_eq!(parse("{1 + 1: 2}[2]"), Ok(Expr::Index {
+			object: Box::new(Expr::Map(vec![(
+				Expr::Binary {
+					left: number(1.0),
+					op: BinaryOp::Add,
+					right: number(1.0)
+				},
+				Expr::Literal(Literal::Number(2.0))
+			)])),
+			index: number(2.0)
+		}));
+		assert_eq!(parse("{\"a\" 1}").unwrap_err().message(), "Expected ':' after map key, found '1'");
+		assert_eq!(parse("{\"a\": 1").unwrap_err().message(), "Expected '}' after map entries, found end of input");
+	}
+
+	#[test]
+	fn test_map_or_block() {
+		assert_eq!(parse_program("{}"), Ok(vec![Stmt::Block(vec![])]));
+		assert_eq!(parse_program("var m = {};"), Ok(vec![Stmt::Var {
+			name: String::from("m"),
+			initializer: Some(Expr::Map(vec![]))
+		}]));
+		assert_eq!(parse_program("{x: 1};"), Ok(vec![Stmt::Expression(Expr::Map(vec![
+			(Expr::Variable(String::from("x")), Expr::Literal(Literal::Number(1.0)))
+		]))]));
+		assert_eq!(parse_program("{x;}"), Ok(vec![Stmt::Block(vec![
+			Stmt::Expression(Expr::Variable(String::from("x")))
+		])]));
+		assert_eq!(parse_program("({});"), Ok(vec![Stmt::Expression(Expr::Grouping(Box::new(Expr::Map(vec![]))))]));
+	}
+
+	#[test]
+	fn test_error_recovery() {
+		let source = "var a = 1\nprint a;\nprint (1 + ;\nvar b = 2;\nvar = 3;\nprint b;\n";
+		let output = Parser::new_spanned(tokenize_spanned(source).unwrap(), source).parse_lossy();
+		let errors: Vec<_> = output.errors.iter().map(ParseError::to_string).collect();
+		assert_eq!(errors, vec![
+			String::from("[1:10] Error: Expected ';' after variable declaration, found 'print'"),
+			String::from("[3:12] Error: Expected expression, found ';'"),
+			String::from("[5:5] Error: Expected variable name, found '='")
+		]);
+		assert_eq!(output.statements, vec![
+			Stmt::Print(Expr::Variable(String::from("a"))),
+			Stmt::Var {
+				name: String::from("b"),
+				initializer: Some(Expr::Literal(Literal::Number(2.0)))
+			},
+			Stmt::Print(Expr::Variable(String::from("b")))
+		]);
+		assert_eq!(parse_program(source).unwrap_err().len(), 3);
+
+		// A missing `;` doesn't hide an error in the statement after it
+		let source = "print 1\nprint x = ;\nprint 3;";
+		let output = Parser::new_spanned(tokenize_spanned(source).unwrap(), source).parse_lossy();
+		let errors: Vec<_> = output.errors.iter().map(ParseError::to_string).collect();
+		assert_eq!(errors, vec![
+			String::from("[1:8] Error: Expected ';' after value, found 'print'"),
+			String::from("[2:11] Error: Expected expression, found ';'")
+		]);
+		assert_eq!(output.statements, vec![Stmt::Print(Expr::Literal(Literal::Number(3.0)))]);
+	}
+
+	#[test]
+	fn test_error_recovery_in_blocks() {
+		let source = "{\n\tprint ;\n\tprint 1;\n\tx = ;\n}\nprint 2;";
+		let output = Parser::new_spanned(tokenize_spanned(source).unwrap(), source).parse_lossy();
+		let errors: Vec<_> = output.errors.iter().map(ParseError::to_string).collect();
+		assert_eq!(errors, vec![
+			String::from("[2:8] Error: Expected expression, found ';'"),
+			String::from("[4:6] Error: Expected expression, found ';'")
+		]);
+		assert_eq!(output.statements, vec![
+			Stmt::Block(vec![Stmt::Print(Expr::Literal(Literal::Number(1.0)))]),
+			Stmt::Print(Expr::Literal(Literal::Number(2.0)))
+		]);
+
+		// Recovering at a statement keyword rather than a `;` keeps the statement that starts there
+		let output = Parser::new(tokenize("var 1 print 2; print )").unwrap()).parse_lossy();
+		assert_eq!(output.errors.len(), 2);
+		assert_eq!(output.statements, vec![Stmt::Print(Expr::Literal(Literal::Number(2.0)))]);
+
+		// Recovery inside a block stops at its `}` instead of eating it
+		let source = "{ print 1 }\nprint 2;";
+		let output = Parser::new_spanned(tokenize_spanned(source).unwrap(), source).parse_lossy();
+		let errors: Vec<_> = output.errors.iter().map(ParseError::to_string).collect();
+		assert_eq!(errors, vec![String::from("[1:10] Error: Expected ';' after value, found '}'")]);
+		assert_eq!(output.statements, vec![
+			Stmt::Block(vec![]),
+			Stmt::Print(Expr::Literal(Literal::Number(2.0)))
+		]);
+		assert_eq!(parse_program("var b = 2;\n}\nprint b;").unwrap_err(), vec![
+			String::from("[2:1] Error: Expected expression, found '}'")
+		]);
+	}
+}
\ No newline at end of file
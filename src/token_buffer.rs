@@ -0,0 +1,168 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::tokenizer::{Span, Spanned, Token, TokenKind};
+
+/// Marks a token without an entry in any of the side tables
+const NO_PAYLOAD: u32 = u32::MAX;
+
+/// A structure-of-arrays alternative to `Vec<Spanned<Token>>`, owning all of its payloads.
+///
+/// Kinds, spans and payload indices live in parallel arrays so that a parser can branch on `kinds()`
+/// without pulling payloads into cache.  Identifiers are interned, so repeated names share one entry.
+#[derive(Debug, Default)]
+pub struct TokenBuffer {
+	kinds: Vec<TokenKind>,
+	starts: Vec<u32>,
+	lengths: Vec<u32>,
+	payloads: Vec<u32>,
+
+	identifiers: Vec<String>,
+	identifier_lookup: HashMap<String, u32>,
+	// Also holds the text of comment and whitespace tokens
+	strings: Vec<String>,
+	numbers: Vec<f64>
+}
+
+impl TokenBuffer {
+	pub fn new() -> TokenBuffer {
+		TokenBuffer::default()
+	}
+
+	pub fn push(&mut self, token: Spanned<Token<'_>>) {
+		let kind = token.value.kind();
+		let payload = match token.value {
+			Token::Identifier(name) => {
+				if let Some(&index) = self.identifier_lookup.get(&*name) {
+					index
+				} else {
+					let index = self.identifiers.len() as u32;
+					self.identifiers.push(name.to_string());
+					self.identifier_lookup.insert(name.into_owned(), index);
+					index
+				}
+			},
+			Token::String(value) | Token::Comment(value) | Token::Whitespace(value) => {
+				self.strings.push(value.into_owned());
+				self.strings.len() as u32 - 1
+			},
+			Token::Number(value) => {
+				self.numbers.push(value);
+				self.numbers.len() as u32 - 1
+			},
+			_ => NO_PAYLOAD
+		};
+
+		self.kinds.push(kind);
+		self.starts.push(token.span.start);
+		self.lengths.push(token.span.end - token.span.start);
+		self.payloads.push(payload);
+	}
+
+	pub fn len(&self) -> usize {
+		self.kinds.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.kinds.is_empty()
+	}
+
+	pub fn kinds(&self) -> &[TokenKind] {
+		&self.kinds
+	}
+
+	pub fn kind(&self, index: usize) -> TokenKind {
+		self.kinds[index]
+	}
+
+	pub fn span(&self, index: usize) -> Span {
+		let start = self.starts[index];
+		Span {
+			start,
+			end: start + self.lengths[index]
+		}
+	}
+
+	pub fn number(&self, index: usize) -> Option<f64> {
+		match self.kinds[index] {
+			TokenKind::Number => Some(self.numbers[self.payloads[index] as usize]),
+			_ => None
+		}
+	}
+
+	pub fn string(&self, index: usize) -> Option<&str> {
+		match self.kinds[index] {
+			TokenKind::String => Some(&self.strings[self.payloads[index] as usize]),
+			_ => None
+		}
+	}
+
+	pub fn identifier(&self, index: usize) -> Option<&str> {
+		match self.kinds[index] {
+			TokenKind::Identifier => Some(&self.identifiers[self.payloads[index] as usize]),
+			_ => None
+		}
+	}
+
+	/// Rebuilds the classic `Token` at `index`, borrowing its payload from the buffer
+	pub fn token(&self, index: usize) -> Token<'_> {
+		match self.kinds[index] {
+			TokenKind::Identifier => Token::Identifier(Cow::Borrowed(self.identifier(index).unwrap())),
+			TokenKind::String => Token::String(Cow::Borrowed(self.string(index).unwrap())),
+			TokenKind::Number => Token::Number(self.number(index).unwrap()),
+			TokenKind::Comment => Token::Comment(Cow::Borrowed(&self.strings[self.payloads[index] as usize])),
+			TokenKind::Whitespace => Token::Whitespace(Cow::Borrowed(&self.strings[self.payloads[index] as usize])),
+			kind => kind.fixed_token().unwrap()
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = Token<'_>> {
+		(0..self.len()).map(|index| self.token(index))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tokenizer::Tokenizer;
+
+	static SOURCE: &str = r#"
+	var greeting = "Hello";
+	var count = 1.5;
+	while (count <= 10) {
+		print greeting + "!";
+		count = count * 2;
+	}
+	// greeting again
+	print greeting != nil and !false;
+	"#;
+
+	#[test]
+	fn test_matches_classic_tokens() {
+		let classic = Tokenizer::new(SOURCE).tokenize_spanned().unwrap();
+		let buffer = Tokenizer::new(SOURCE).tokenize_buffered().unwrap();
+
+		assert_eq!(buffer.len(), classic.len());
+		assert_eq!(buffer.iter().collect::<Vec<_>>(), Tokenizer::new(SOURCE).tokenize().unwrap());
+		for (index, token) in classic.iter().enumerate() {
+			assert_eq!(buffer.kind(index), token.value.kind());
+			assert_eq!(buffer.span(index), token.span);
+		}
+	}
+
+	#[test]
+	fn test_accessors() {
+		let buffer = Tokenizer::new("x = 2; print x + \"x\";").tokenize_buffered().unwrap();
+
+		assert_eq!(buffer.kinds()[..4], [TokenKind::Identifier, TokenKind::Equal, TokenKind::Number, TokenKind::Semicolon]);
+		assert_eq!(buffer.identifier(0), Some("x"));
+		assert_eq!(buffer.number(2), Some(2.0));
+		assert_eq!(buffer.number(0), None);
+		assert_eq!(buffer.string(7), Some("x"));
+		assert_eq!(buffer.identifier(7), None);
+		assert_eq!(buffer.kind(buffer.len() - 1), TokenKind::Eof);
+
+		// Both uses of `x` share one interned identifier
+		assert_eq!(buffer.identifiers.len(), 1);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,87 @@
+use crate::line_index::LineIndex;
+use crate::tokenizer::{Spanned, Token};
+
+/// Renders `tokens` as a table with one row per token, giving its line, column, kind and source text
+pub fn print_token_table(tokens: &[Spanned<Token>], source: &str) -> String {
+	let header = [String::from("Line"), String::from("Col"), String::from("Kind"), String::from("Lexeme")];
+
+	let line_index = LineIndex::new(source);
+	let rows: Vec<[String; 4]> = tokens.iter().map(|token| {
+		let start = token.span.start as usize;
+		let end = token.span.end as usize;
+		let (line, column) = line_index.line_col(start);
+		[
+			line.to_string(),
+			column.to_string(),
+			format!("{:?}", token.value.kind()),
+			escape_control(&source[start..end])
+		]
+	}).collect();
+
+	let mut widths = header.clone().map(|cell| cell.chars().count());
+	for row in &rows {
+		for (width, cell) in widths.iter_mut().zip(row) {
+			*width = (*width).max(cell.chars().count());
+		}
+	}
+
+	let mut table = String::new();
+	for row in std::iter::once(&header).chain(&rows) {
+		let line = format!(
+			"{:>line_width$}  {:>column_width$}  {:<kind_width$}  {}",
+			row[0], row[1], row[2], row[3],
+			line_width = widths[0],
+			column_width = widths[1],
+			kind_width = widths[2]
+		);
+		table.push_str(line.trim_end());
+		table.push('\n');
+	}
+
+	table
+}
+
+/// Multi-line strings would break the table apart, so newlines, tabs and the like are shown escaped
+fn escape_control(lexeme: &str) -> String {
+	lexeme.chars().map(|c| {
+		if c.is_control() {
+			c.escape_default().to_string()
+		} else {
+			c.to_string()
+		}
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tokenizer::Tokenizer;
+
+	#[test]
+	fn test_print_token_table() {
+		let source = "var greeting = \"hi\";\n\nprint greeting;\"a\nb\"";
+		let tokens = Tokenizer::new(source).tokenize_spanned().unwrap();
+		let table = print_token_table(&tokens, source);
+		let lines: Vec<&str> = table.lines().collect();
+
+		assert_eq!(lines.len(), tokens.len() + 1);
+		assert_eq!(lines[0], "Line  Col  Kind        Lexeme");
+		assert_eq!(lines[1], "   1    1  Var         var");
+		assert_eq!(lines[4], "   1   16  String      \"hi\"");
+		assert_eq!(lines[7], "   3    7  Identifier  greeting");
+		assert_eq!(lines[9], "   3   16  String      \"a\\nb\"");
+		assert_eq!(lines[10], "   4    3  Eof");
+	}
+
+	#[test]
+	fn test_large_table_is_fast() {
+		// Small enough to take a few milliseconds, but rescanning the source for each row would take seconds
+		let source = "var x = 1;\n".repeat(5_000);
+		let tokens = Tokenizer::new(&source).tokenize_spanned().unwrap();
+
+		let started = std::time::Instant::now();
+		let table = print_token_table(&tokens, &source);
+		assert!(started.elapsed() < std::time::Duration::from_secs(1));
+		assert_eq!(table.lines().nth(25_000), Some("5000   10  Semicolon   ;"));
+	}
+}
\ No newline at end of file
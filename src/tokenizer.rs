@@ -1,257 +1,1172 @@
-#[derive(Debug, PartialEq)]
-pub enum Token {
-	LeftParen, RightParen, LeftBrace, RightBrace,
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::VecDeque;
+
+use crate::line_index::LineIndex;
+use crate::token_buffer::TokenBuffer;
+
+/// A scanned token.  Identifiers and strings borrow their text from the source, and only own it when
+/// escape sequences meant the value had to be rebuilt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'src> {
+	LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
 	Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
 
 	Bang, BangEqual,
+	Colon, ColonEqual,
 	Equal, EqualEqual,
-	Greater, GreatEqual,
-	Less, LessEqual,
+	Greater, GreatEqual, GreaterGreater,
+	Less, LessEqual, LessLess,
 
-	Identifier(String),
-	String(String),
+	Identifier(Cow<'src, str>),
+	String(Cow<'src, str>),
 	Number(f64),
 
-	And, Class, Else, False, Fun, For, If, Nil, Or,
+	/// Only produced with `TokenizerOptions::trivia`.  The whole comment, including its `//` or `/* */`.
+	Comment(Cow<'src, str>),
+	/// Only produced with `TokenizerOptions::trivia`.  A run of whitespace, newlines included.
+	Whitespace(Cow<'src, str>),
+
+	And, Assert, Class, Do, Else, False, Fun, For, If, Nil, Or,
+	Print, Return, Super, This, True, Var, While,
+
+	Eof
+}
+
+/// The payload-free discriminant of a `Token`.  Unlike `Token`, whose `Number` payload rules out `Eq` and `Hash`,
+/// this can be used as a map key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum TokenKind {
+	LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
+	Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+
+	Bang, BangEqual,
+	Colon, ColonEqual,
+	Equal, EqualEqual,
+	Greater, GreatEqual, GreaterGreater,
+	Less, LessEqual, LessLess,
+
+	Identifier,
+	String,
+	Number,
+
+	Comment,
+	Whitespace,
+
+	And, Assert, Class, Do, Else, False, Fun, For, If, Nil, Or,
 	Print, Return, Super, This, True, Var, While,
 
 	Eof
 }
 
-pub struct Tokenizer {
-	source: String,
+impl Token<'_> {
+	/// Detaches the token from the source it was scanned from
+	pub fn into_owned(self) -> Token<'static> {
+		match self {
+			Token::Identifier(name) => Token::Identifier(Cow::Owned(name.into_owned())),
+			Token::String(value) => Token::String(Cow::Owned(value.into_owned())),
+			Token::Number(num) => Token::Number(num),
+			Token::Comment(text) => Token::Comment(Cow::Owned(text.into_owned())),
+			Token::Whitespace(text) => Token::Whitespace(Cow::Owned(text.into_owned())),
+			token => token.kind().fixed_token().unwrap()
+		}
+	}
+
+	pub fn kind(&self) -> TokenKind {
+		match self {
+			Token::LeftParen => TokenKind::LeftParen,
+			Token::RightParen => TokenKind::RightParen,
+			Token::LeftBrace => TokenKind::LeftBrace,
+			Token::RightBrace => TokenKind::RightBrace,
+			Token::LeftBracket => TokenKind::LeftBracket,
+			Token::RightBracket => TokenKind::RightBracket,
+			Token::Comma => TokenKind::Comma,
+			Token::Dot => TokenKind::Dot,
+			Token::Minus => TokenKind::Minus,
+			Token::Plus => TokenKind::Plus,
+			Token::Semicolon => TokenKind::Semicolon,
+			Token::Slash => TokenKind::Slash,
+			Token::Star => TokenKind::Star,
+			Token::Bang => TokenKind::Bang,
+			Token::BangEqual => TokenKind::BangEqual,
+			Token::Colon => TokenKind::Colon,
+			Token::ColonEqual => TokenKind::ColonEqual,
+			Token::Equal => TokenKind::Equal,
+			Token::EqualEqual => TokenKind::EqualEqual,
+			Token::Greater => TokenKind::Greater,
+			Token::GreatEqual => TokenKind::GreatEqual,
+			Token::GreaterGreater => TokenKind::GreaterGreater,
+			Token::Less => TokenKind::Less,
+			Token::LessEqual => TokenKind::LessEqual,
+			Token::LessLess => TokenKind::LessLess,
+			Token::Identifier(_) => TokenKind::Identifier,
+			Token::String(_) => TokenKind::String,
+			Token::Number(_) => TokenKind::Number,
+			Token::Comment(_) => TokenKind::Comment,
+			Token::Whitespace(_) => TokenKind::Whitespace,
+			Token::And => TokenKind::And,
+			Token::Assert => TokenKind::Assert,
+			Token::Class => TokenKind::Class,
+			Token::Do => TokenKind::Do,
+			Token::Else => TokenKind::Else,
+			Token::False => TokenKind::False,
+			Token::Fun => TokenKind::Fun,
+			Token::For => TokenKind::For,
+			Token::If => TokenKind::If,
+			Token::Nil => TokenKind::Nil,
+			Token::Or => TokenKind::Or,
+			Token::Print => TokenKind::Print,
+			Token::Return => TokenKind::Return,
+			Token::Super => TokenKind::Super,
+			Token::This => TokenKind::This,
+			Token::True => TokenKind::True,
+			Token::Var => TokenKind::Var,
+			Token::While => TokenKind::While,
+			Token::Eof => TokenKind::Eof
+		}
+	}
+
+	/// Comments and whitespace, which only show up with `TokenizerOptions::trivia`
+	pub fn is_trivia(&self) -> bool {
+		matches!(self, Token::Comment(_) | Token::Whitespace(_))
+	}
+
+	/// Source text that scans back to this token.  Numbers are written in their shortest form, so `1.0` comes
+	/// out as `1`, and strings are quoted with anything that needs it escaped.  `Eof` has no text, and trivia is its original text.
+	pub fn lexeme(&self) -> Cow<'_, str> {
+		Cow::Borrowed(match self {
+			Token::Identifier(name) => return Cow::Borrowed(name),
+			Token::Comment(text) | Token::Whitespace(text) => return Cow::Borrowed(text),
+			Token::String(value) => return Cow::Owned(quote_string(value)),
+			Token::Number(num) => return Cow::Owned(num.to_string()),
+			Token::LeftParen => "(",
+			Token::RightParen => ")",
+			Token::LeftBrace => "{",
+			Token::RightBrace => "}",
+			Token::LeftBracket => "[",
+			Token::RightBracket => "]",
+			Token::Comma => ",",
+			Token::Dot => ".",
+			Token::Minus => "-",
+			Token::Plus => "+",
+			Token::Semicolon => ";",
+			Token::Slash => "/",
+			Token::Star => "*",
+			Token::Bang => "!",
+			Token::BangEqual => "!=",
+			Token::Colon => ":",
+			Token::ColonEqual => ":=",
+			Token::Equal => "=",
+			Token::EqualEqual => "==",
+			Token::Greater => ">",
+			Token::GreatEqual => ">=",
+			Token::GreaterGreater => ">>",
+			Token::Less => "<",
+			Token::LessEqual => "<=",
+			Token::LessLess => "<<",
+			Token::And => "and",
+			Token::Assert => "assert",
+			Token::Class => "class",
+			Token::Do => "do",
+			Token::Else => "else",
+			Token::False => "false",
+			Token::Fun => "fun",
+			Token::For => "for",
+			Token::If => "if",
+			Token::Nil => "nil",
+			Token::Or => "or",
+			Token::Print => "print",
+			Token::Return => "return",
+			Token::Super => "super",
+			Token::This => "this",
+			Token::True => "true",
+			Token::Var => "var",
+			Token::While => "while",
+			Token::Eof => ""
+		})
+	}
+}
+
+/// Renders the token as it would appear in source, except for `Eof`, which is shown as `<eof>`
+impl std::fmt::Display for Token<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Token::Eof => write!(f, "<eof>"),
+			token => write!(f, "{}", token.lexeme())
+		}
+	}
+}
+
+/// Writes `value` as a string literal, escaping quotes, backslashes and control characters
+fn quote_string(value: &str) -> String {
+	let mut quoted = String::with_capacity(value.len() + 2);
+	quoted.push('"');
+	for c in value.chars() {
+		match c {
+			'"' => quoted.push_str("\\\""),
+			'\\' => quoted.push_str("\\\\"),
+			'\n' => quoted.push_str("\\n"),
+			'\t' => quoted.push_str("\\t"),
+			'\r' => quoted.push_str("\\r"),
+			'\0' => quoted.push_str("\\0"),
+			c if c.is_control() => quoted.push_str(&format!("\\u{{{:X}}}", c as u32)),
+			c => quoted.push(c)
+		}
+	}
+	quoted.push('"');
+	quoted
+}
+
+impl TokenKind {
+	/// Every kind, ordered by discriminant
+	pub const ALL: [TokenKind; 49] = [
+		TokenKind::LeftParen, TokenKind::RightParen, TokenKind::LeftBrace, TokenKind::RightBrace, TokenKind::LeftBracket, TokenKind::RightBracket,
+		TokenKind::Comma, TokenKind::Dot, TokenKind::Minus, TokenKind::Plus, TokenKind::Semicolon, TokenKind::Slash, TokenKind::Star,
+
+		TokenKind::Bang, TokenKind::BangEqual,
+		TokenKind::Colon, TokenKind::ColonEqual,
+		TokenKind::Equal, TokenKind::EqualEqual,
+		TokenKind::Greater, TokenKind::GreatEqual, TokenKind::GreaterGreater,
+		TokenKind::Less, TokenKind::LessEqual, TokenKind::LessLess,
+
+		TokenKind::Identifier,
+		TokenKind::String,
+		TokenKind::Number,
+
+		TokenKind::Comment,
+		TokenKind::Whitespace,
+
+		TokenKind::And, TokenKind::Assert, TokenKind::Class, TokenKind::Do, TokenKind::Else, TokenKind::False, TokenKind::Fun, TokenKind::For, TokenKind::If, TokenKind::Nil, TokenKind::Or,
+		TokenKind::Print, TokenKind::Return, TokenKind::Super, TokenKind::This, TokenKind::True, TokenKind::Var, TokenKind::While,
+
+		TokenKind::Eof
+	];
+
+	pub fn from_u8(value: u8) -> Option<TokenKind> {
+		TokenKind::ALL.get(value as usize).copied()
+	}
+
+	/// The token of this kind, for every kind that doesn't carry a payload
+	pub fn fixed_token(self) -> Option<Token<'static>> {
+		Some(match self {
+			TokenKind::LeftParen => Token::LeftParen,
+			TokenKind::RightParen => Token::RightParen,
+			TokenKind::LeftBrace => Token::LeftBrace,
+			TokenKind::RightBrace => Token::RightBrace,
+			TokenKind::LeftBracket => Token::LeftBracket,
+			TokenKind::RightBracket => Token::RightBracket,
+			TokenKind::Comma => Token::Comma,
+			TokenKind::Dot => Token::Dot,
+			TokenKind::Minus => Token::Minus,
+			TokenKind::Plus => Token::Plus,
+			TokenKind::Semicolon => Token::Semicolon,
+			TokenKind::Slash => Token::Slash,
+			TokenKind::Star => Token::Star,
+			TokenKind::Bang => Token::Bang,
+			TokenKind::BangEqual => Token::BangEqual,
+			TokenKind::Colon => Token::Colon,
+			TokenKind::ColonEqual => Token::ColonEqual,
+			TokenKind::Equal => Token::Equal,
+			TokenKind::EqualEqual => Token::EqualEqual,
+			TokenKind::Greater => Token::Greater,
+			TokenKind::GreatEqual => Token::GreatEqual,
+			TokenKind::GreaterGreater => Token::GreaterGreater,
+			TokenKind::Less => Token::Less,
+			TokenKind::LessEqual => Token::LessEqual,
+			TokenKind::LessLess => Token::LessLess,
+			TokenKind::Identifier | TokenKind::String | TokenKind::Number | TokenKind::Comment | TokenKind::Whitespace => return None,
+			TokenKind::And => Token::And,
+			TokenKind::Assert => Token::Assert,
+			TokenKind::Class => Token::Class,
+			TokenKind::Do => Token::Do,
+			TokenKind::Else => Token::Else,
+			TokenKind::False => Token::False,
+			TokenKind::Fun => Token::Fun,
+			TokenKind::For => Token::For,
+			TokenKind::If => Token::If,
+			TokenKind::Nil => Token::Nil,
+			TokenKind::Or => Token::Or,
+			TokenKind::Print => Token::Print,
+			TokenKind::Return => Token::Return,
+			TokenKind::Super => Token::Super,
+			TokenKind::This => Token::This,
+			TokenKind::True => Token::True,
+			TokenKind::Var => Token::Var,
+			TokenKind::While => Token::While,
+			TokenKind::Eof => Token::Eof
+		})
+	}
+}
+
+/// Byte range of a token within the source, `start..end`.
+///
+/// Offsets are stored as `u32` to keep token vectors small, which limits sources to `u32::MAX` bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+	pub start: u32,
+	pub end: u32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+	pub value: T,
+	pub span: Span
+}
+
+impl<T> Spanned<T> {
+	/// Offsets must already be known to fit in a `u32` (see `check_source_len`).
+	fn new(value: T, start: usize, end: usize) -> Spanned<T> {
+		debug_assert!(start <= end && end <= u32::MAX as usize);
+		Spanned {
+			value,
+			span: Span {
+				start: start as u32,
+				end: end as u32
+			}
+		}
+	}
+}
+
+/// A point in the source: the byte offset along with its 1-based line and column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Location {
+	pub offset: usize,
+	pub line: usize,
+	pub column: usize
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LexError {
+	UnexpectedCharacter {
+		ch: char,
+		location: Location
+	},
+	/// `location` points at the opening quote
+	UnterminatedString {
+		location: Location
+	},
+	InvalidNumber {
+		text: String,
+		location: Location
+	},
+	/// A number with a second fractional part, like `1.2.3`, `location` points at the second `.`
+	UnexpectedDotInNumber {
+		location: Location
+	},
+	/// `location` points at the opening `/*`
+	UnterminatedBlockComment {
+		location: Location
+	},
+	/// An unknown or malformed escape sequence, `location` points at the backslash
+	InvalidEscape {
+		text: String,
+		reason: EscapeError,
+		location: Location
+	},
+	SourceTooLarge {
+		len: usize
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EscapeError {
+	/// A backslash followed by something that isn't a known escape, like `\q`
+	Unknown,
+	/// Missing or non-hexadecimal digits, like `\x4G` or `\u{}`
+	InvalidHex,
+	/// `\u` without its `{` or `}`
+	MissingBrace,
+	/// A code point above `U+10FFFF`
+	OutOfRange,
+	/// A code point in the surrogate range `U+D800..=U+DFFF`
+	Surrogate
+}
+
+impl LexError {
+	pub fn location(&self) -> Option<Location> {
+		match self {
+			LexError::UnexpectedCharacter { location, .. } => Some(*location),
+			LexError::UnterminatedString { location } => Some(*location),
+			LexError::InvalidNumber { location, .. } => Some(*location),
+			LexError::UnexpectedDotInNumber { location } => Some(*location),
+			LexError::UnterminatedBlockComment { location } => Some(*location),
+			LexError::InvalidEscape { location, .. } => Some(*location),
+			LexError::SourceTooLarge { .. } => None
+		}
+	}
+
+	pub fn message(&self) -> String {
+		match self {
+			LexError::UnexpectedCharacter { ch, .. } => format!("Invalid token '{}'", ch),
+			LexError::UnterminatedString { .. } => String::from("Unterminated string"),
+			LexError::InvalidNumber { text, .. } => format!("Invalid number '{}'", text),
+			LexError::UnexpectedDotInNumber { .. } => String::from("Unexpected '.' in number literal"),
+			LexError::UnterminatedBlockComment { .. } => String::from("Unterminated block comment"),
+			LexError::InvalidEscape { text, reason, .. } => match reason {
+				EscapeError::Unknown => format!("Invalid escape sequence '{}'", text),
+				EscapeError::InvalidHex => format!("Invalid hex digits in escape sequence '{}'", text),
+				EscapeError::MissingBrace => format!("Missing brace in unicode escape '{}'", text),
+				EscapeError::OutOfRange => format!("Unicode escape '{}' is out of range", text),
+				EscapeError::Surrogate => format!("Unicode escape '{}' is a surrogate, which can't be stored in a string", text)
+			},
+			LexError::SourceTooLarge { len } => format!("Source too large ({} bytes, the maximum is {} bytes)", len, u32::MAX)
+		}
+	}
+}
+
+impl std::fmt::Display for LexError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self.location() {
+			Some(location) => write!(f, "[{}:{}] Error: {}", location.line, location.column, self.message()),
+			None => write!(f, "Error: {}", self.message())
+		}
+	}
+}
+
+impl std::error::Error for LexError {}
+
+/// Everything scanned from a source: the tokens that were recovered (still ending in `Eof`) and any errors
+#[derive(Debug, Default, PartialEq)]
+pub struct LexOutput<'src> {
+	pub tokens: Vec<Spanned<Token<'src>>>,
+	pub errors: Vec<LexError>
+}
+
+impl<'src> LexOutput<'src> {
+	/// The comments and whitespace after the last real token, which lead up to `Eof`.  Empty unless scanned
+	/// with `TokenizerOptions::trivia`.
+	pub fn eof_leading_trivia(&self) -> &[Spanned<Token<'src>>] {
+		let end = match self.tokens.last() {
+			Some(token) if token.value == Token::Eof => self.tokens.len() - 1,
+			_ => self.tokens.len()
+		};
+		let start = self.tokens[..end].iter().rposition(|token| !token.value.is_trivia()).map_or(0, |index| index + 1);
+		&self.tokens[start..end]
+	}
+}
+
+/// Tweaks to how source is scanned.  Every option is off by default, which gives the standard Lox behaviour.
+///
+/// Options can be set field by field or chained:
+///
+/// ```
+/// use lox_rs::tokenizer::TokenizerOptions;
+///
+/// let options = TokenizerOptions::new().trivia(true).max_errors(Some(10));
+/// assert!(options.trivia);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TokenizerOptions {
+	/// Treat the curly quotes `“` and `”` (often left behind by word processors) as `"`
+	pub smart_quotes: bool,
+	/// Identifiers are normally `[A-Za-z_][A-Za-z0-9_]*`, this also allows any Unicode letters and digits in them
+	pub unicode_identifiers: bool,
+	/// Stop scanning once this many errors have been produced.  The stream still ends with `Eof`, placed
+	/// where scanning stopped.
+	pub max_errors: Option<usize>,
+	/// Emit comments and whitespace as `Token::Comment` and `Token::Whitespace` instead of skipping them, so
+	/// the tokens cover every byte of the source
+	pub trivia: bool,
+	/// Accept `,` as the decimal separator too, as in `3,14`.  A comma between two digits is always taken as
+	/// part of a number, so `f(1,2)` becomes `f(1.2)`; separate arguments with a space (`f(1, 2)`) when this
+	/// is on.
+	pub decimal_comma: bool,
+	/// Leave backslashes in strings alone rather than treating them as escapes, so `"\n"` is a backslash and an
+	/// `n`.  A `"` always ends the string.
+	pub raw_strings: bool,
+	/// Reject strings with a raw newline in them, reporting them as unterminated at the end of their line
+	pub single_line_strings: bool
+}
+
+impl TokenizerOptions {
+	pub fn new() -> TokenizerOptions {
+		TokenizerOptions::default()
+	}
+
+	pub fn smart_quotes(mut self, smart_quotes: bool) -> TokenizerOptions {
+		self.smart_quotes = smart_quotes;
+		self
+	}
+
+	pub fn unicode_identifiers(mut self, unicode_identifiers: bool) -> TokenizerOptions {
+		self.unicode_identifiers = unicode_identifiers;
+		self
+	}
+
+	pub fn max_errors(mut self, max_errors: Option<usize>) -> TokenizerOptions {
+		self.max_errors = max_errors;
+		self
+	}
+
+	pub fn trivia(mut self, trivia: bool) -> TokenizerOptions {
+		self.trivia = trivia;
+		self
+	}
+
+	pub fn decimal_comma(mut self, decimal_comma: bool) -> TokenizerOptions {
+		self.decimal_comma = decimal_comma;
+		self
+	}
+
+	pub fn raw_strings(mut self, raw_strings: bool) -> TokenizerOptions {
+		self.raw_strings = raw_strings;
+		self
+	}
+
+	pub fn single_line_strings(mut self, single_line_strings: bool) -> TokenizerOptions {
+		self.single_line_strings = single_line_strings;
+		self
+	}
+}
+
+pub struct Tokenizer<'src> {
+	source: &'src str,
+	options: TokenizerOptions,
 	offset: usize,
-	tokens: Vec<Token>
+	// The 1-based line and column of `offset`, kept up to date as characters are consumed
+	line: usize,
+	column: usize,
+	// Where the token currently being scanned began
+	token_start: Location,
+	// Only built if a location is needed somewhere the scan isn't tracking
+	line_index: OnceCell<LineIndex<'src>>,
+	// Results that were produced together (e.g. escape errors found inside a string) but are handed out one at a time
+	pending: VecDeque<Result<Spanned<Token<'src>>, LexError>>,
+	error_count: usize,
+	finished: bool
 }
 
 static OPERATORS: &str = "!=><";
 
-impl Tokenizer {
-	pub fn new(source: impl Into<String>) -> Tokenizer {
+#[cfg(not(test))]
+fn max_source_len() -> usize {
+	u32::MAX as usize
+}
+
+// Lets tests reach the too-large path without building a 4 GiB source.  Each test runs on its own thread.
+#[cfg(test)]
+thread_local! {
+	static MAX_SOURCE_LEN: std::cell::Cell<usize> = const { std::cell::Cell::new(u32::MAX as usize) };
+}
+
+#[cfg(test)]
+fn max_source_len() -> usize {
+	MAX_SOURCE_LEN.with(|max| max.get())
+}
+
+fn check_source_len(len: usize) -> Result<usize, LexError> {
+	if len > max_source_len() {
+		return Err(LexError::SourceTooLarge { len });
+	}
+	Ok(len)
+}
+
+/// Maps a byte offset to a 1-based (line, column) pair, where columns count characters rather than bytes
+///
+/// Builds a `LineIndex` every time, so use one directly when looking up more than one offset.  Out of range
+/// offsets are clamped the same way as in `LineIndex::line_col`.
+pub fn get_2d_location(source: &str, offset: usize) -> (usize, usize) {
+	LineIndex::new(source).line_col(offset)
+}
+
+/// Underscores may separate digits in a number literal, but only with a digit on both sides
+fn valid_digit_separators(literal: &str) -> bool {
+	let chars: Vec<char> = literal.chars().collect();
+	chars.iter().enumerate().all(|(i, &c)| {
+		c != '_' || (
+			i > 0 && chars[i - 1].is_ascii_alphanumeric() &&
+			i + 1 < chars.len() && chars[i + 1].is_ascii_alphanumeric()
+		)
+	})
+}
+
+/// Decodes the body of a `\xHH` or `\u{...}` escape, starting just after the `x`/`u`.
+///
+/// Returns the decoded character (or why the escape is malformed) along with the offset just past
+/// everything that was consumed.
+fn decode_escape(bytes: &[u8], offset: usize, kind: char) -> (Result<char, EscapeError>, usize) {
+	let mut end = offset;
+
+	if kind == 'x' {
+		while end < offset + 2 && end < bytes.len() && bytes[end].is_ascii_hexdigit() {
+			end += 1;
+		}
+		if end - offset != 2 {
+			return (Err(EscapeError::InvalidHex), end);
+		}
+		let value = u8::from_str_radix(std::str::from_utf8(&bytes[offset..end]).unwrap(), 16).unwrap();
+		return (Ok(value as char), end);
+	}
+
+	if end >= bytes.len() || bytes[end] != b'{' {
+		return (Err(EscapeError::MissingBrace), end);
+	}
+	end += 1;
+
+	let digits_start = end;
+	while end < bytes.len() && bytes[end].is_ascii_hexdigit() {
+		end += 1;
+	}
+	let digits_end = end;
+
+	if end < bytes.len() && bytes[end].is_ascii_alphanumeric() {
+		// Swallow the rest of the bad digit run (and its brace) so it doesn't end up in the string
+		while end < bytes.len() && bytes[end].is_ascii_alphanumeric() {
+			end += 1;
+		}
+		if end < bytes.len() && bytes[end] == b'}' {
+			end += 1;
+		}
+		return (Err(EscapeError::InvalidHex), end);
+	}
+
+	if end >= bytes.len() || bytes[end] != b'}' {
+		return (Err(EscapeError::MissingBrace), end);
+	}
+	end += 1;
+
+	let digits = std::str::from_utf8(&bytes[digits_start..digits_end]).unwrap();
+	if digits.is_empty() {
+		return (Err(EscapeError::InvalidHex), end);
+	}
+
+	let value = match u32::from_str_radix(digits, 16) {
+		Ok(value) if value <= 0x10FFFF => value,
+		_ => return (Err(EscapeError::OutOfRange), end)
+	};
+
+	match char::from_u32(value) {
+		Some(c) => (Ok(c), end),
+		None => (Err(EscapeError::Surrogate), end)
+	}
+}
+
+impl<'src> Tokenizer<'src> {
+	pub fn new(source: &'src str) -> Tokenizer<'src> {
+		Tokenizer::new_with_options(source, TokenizerOptions::default())
+	}
+
+	pub fn new_with_options(source: &'src str, options: TokenizerOptions) -> Tokenizer<'src> {
 		Tokenizer {
-			source: source.into(),
+			source,
+			options,
 			offset: 0,
-			tokens: vec![]
+			line: 1,
+			column: 1,
+			token_start: Location {
+				offset: 0,
+				line: 1,
+				column: 1
+			},
+			line_index: OnceCell::new(),
+			pending: VecDeque::new(),
+			error_count: 0,
+			finished: false
+		}
+	}
+
+	/// The location of the current position
+	fn here(&self) -> Location {
+		Location {
+			offset: self.offset,
+			line: self.line,
+			column: self.column
 		}
 	}
 
-	fn get_2d_location(&self, offset: usize) -> (usize, usize) {
-		let bytes = self.source.as_bytes();
-		let mut line: usize = 1;
-		let mut column: usize = 1;
-		let mut current_offset: usize = 0;
+	fn location(&self, offset: usize) -> Location {
+		if offset == self.token_start.offset {
+			return self.token_start;
+		}
+		if offset == self.offset {
+			return self.here();
+		}
 
-		while current_offset < offset {
-			let c = bytes[current_offset] as char;
+		let (line, column) = self.line_index.get_or_init(|| LineIndex::new(self.source)).line_col(offset);
+		Location {
+			offset,
+			line,
+			column
+		}
+	}
 
-			if c == '\n' {
-				line += 1;
-				column = 1;
-			}
-			current_offset += 1;
-			column += 1;
+	/// Moves past `c`, which must be the character at the current position
+	fn bump(&mut self, c: char) {
+		self.offset += c.len_utf8();
+		if c == '\n' {
+			self.line += 1;
+			self.column = 1;
+		} else {
+			self.column += 1;
 		}
+	}
 
-		(line, column)
+	fn peek(&self) -> Option<char> {
+		self.source[self.offset..].chars().next()
 	}
 
-	fn generate_report(&self, message: impl Into<String>, offset: usize) -> String {
-		let location = self.get_2d_location(offset);
-		format!("[{}:{}] Error: {}", location.0, location.1, message.into())
+	fn peek_next(&self) -> Option<char> {
+		let mut chars = self.source[self.offset..].chars();
+		chars.next();
+		chars.next()
 	}
 
-	pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
-		
-		// Ensure the final token is properly processed
-		// (Otherwise, if an identifier or number is the final token, it'll never be pushed to the result)
-		self.source.push(' ');
+	fn advance(&mut self) -> Option<char> {
+		let c = self.peek()?;
+		self.bump(c);
+		Some(c)
+	}
 
-		let bytes = self.source.as_bytes();
+	fn advance_if(&mut self, expected: char) -> bool {
+		if self.peek() == Some(expected) {
+			self.bump(expected);
+			return true;
+		}
+		false
+	}
 
-		let mut errors = vec![];
+	fn advance_while(&mut self, predicate: impl Fn(char) -> bool) {
+		while let Some(c) = self.peek() {
+			if !predicate(c) {
+				break;
+			}
+			self.bump(c);
+		}
+	}
 
-		let mut reading_string = false;
-		let mut reading_number = false;
-		let mut reading_identifier = false;
-		let mut read_start_offset: usize = 0;
-		let mut escape_next = false;
-		let mut string_buf: Vec<char> = vec![];
+	/// Rewinds to the start of the source, discarding any partially consumed iteration
+	pub fn reset(&mut self) {
+		self.offset = 0;
+		self.line = 1;
+		self.column = 1;
+		self.token_start = self.here();
+		self.pending.clear();
+		self.error_count = 0;
+		self.finished = false;
+	}
 
-		while self.offset < self.source.len() {
-			let c = bytes[self.offset] as char;
-			let current_offset = self.offset;
-			self.offset += 1;
+	/// How many bytes of the source have been scanned so far.  Once scanning is over this is the full length
+	/// of the source, unless `max_errors` cut it short.
+	pub fn bytes_consumed(&self) -> usize {
+		self.offset
+	}
 
-			if reading_string {
-				if escape_next {
-					string_buf.push(c);
-					escape_next = false;
-					continue;
-				}
+	/// Tokenizes the whole source.  This always starts from the beginning, so calling it again (or after
+	/// pulling some tokens through the iterator) gives the same result as the first call.
+	pub fn tokenize(&mut self) -> Result<Vec<Token<'src>>, Vec<LexError>> {
+		let tokens = self.tokenize_spanned()?;
+		Ok(tokens.into_iter().map(|token| token.value).collect())
+	}
 
-				if c == '"' {
-					reading_string = false;
-					self.tokens.push(Token::String(
-						std::mem::take(&mut string_buf)
-						.into_iter()
-						.collect()
-					));
-					continue;
-				}
+	pub fn tokenize_buffered(&mut self) -> Result<TokenBuffer, Vec<LexError>> {
+		self.reset();
 
-				if c == '\\' {
-					escape_next = true;
-					continue;
-				}
+		let mut buffer = TokenBuffer::new();
+		let mut errors = vec![];
 
-				string_buf.push(c);
-				continue;
+		while let Some(result) = self.next_token() {
+			match result {
+				Ok(token) => buffer.push(token),
+				Err(e) => errors.push(e)
 			}
+		}
 
-			if reading_number {
-				if c.is_numeric() || c == '.' {
-					string_buf.push(c);
-					continue;
-				}
+		if !errors.is_empty() {
+			return Err(errors);
+		}
 
-				reading_number = false;
+		Ok(buffer)
+	}
 
-				let num_string: String = std::mem::take(&mut string_buf)
-					.into_iter()
-					.collect();
+	pub fn tokenize_spanned(&mut self) -> Result<Vec<Spanned<Token<'src>>>, Vec<LexError>> {
+		let output = self.tokenize_lossy();
 
-				if let Ok(num) = num_string.parse::<f64>() {
-					self.tokens.push(Token::Number(num));
-				} else {
-					errors.push(self.generate_report(format!("Invalid number '{}'", num_string), read_start_offset));
-				}
-			}
+		if !output.errors.is_empty() {
+			return Err(output.errors);
+		}
 
-			if reading_identifier {
-				if c.is_alphanumeric() {
-					string_buf.push(c);
-					continue;
-				}
+		Ok(output.tokens)
+	}
 
-				reading_identifier = false;
-
-				let iden: String = std::mem::take(&mut string_buf)
-					.into_iter()
-					.collect();
-
-				self.tokens.push(match &iden[..] {
-					"and" => Token::And,
-					"class" => Token::Class,
-					"else" => Token::Else,
-					"false" => Token::False,
-					"fun" => Token::Fun,
-					"for" => Token::For,
-					"if" => Token::If,
-					"nil" => Token::Nil,
-					"or" => Token::Or,
-					"print" => Token::Print,
-					"return" => Token::Return,
-					"super" => Token::Super,
-					"this" => Token::This,
-					"true" => Token::True,
-					"var" => Token::Var,
-					"while" => Token::While,
-					_ => Token::Identifier(iden)
-				});
+	/// Tokenizes the whole source, keeping every token that could be scanned even when there are errors
+	pub fn tokenize_lossy(&mut self) -> LexOutput<'src> {
+		self.reset();
+
+		let mut output = LexOutput::default();
+
+		while let Some(result) = self.next_token() {
+			match result {
+				Ok(token) => output.tokens.push(token),
+				Err(e) => output.errors.push(e)
 			}
+		}
 
-			if c.is_whitespace() {
-				continue;
+		output
+	}
+
+	/// Scans and returns the next token, or the next error if the source is malformed at this point.
+	///
+	/// The final token is always `Token::Eof`, after which this returns `None` forever.
+	pub fn next_token(&mut self) -> Option<Result<Spanned<Token<'src>>, LexError>> {
+		let result = self.scan_token()?;
+
+		if result.is_err() {
+			self.error_count += 1;
+			if !self.finished && self.options.max_errors.is_some_and(|max| self.error_count >= max) {
+				self.pending.clear();
+				self.pending.push_back(Ok(Spanned::new(Token::Eof, self.offset, self.offset)));
+				self.finished = true;
 			}
+		}
 
-			match c {
-				'(' => self.tokens.push(Token::LeftParen),
-				')' => self.tokens.push(Token::RightParen),
-				'{' => self.tokens.push(Token::LeftBrace),
-				'}' => self.tokens.push(Token::RightBrace),
-				',' => self.tokens.push(Token::Comma),
-				'.' => self.tokens.push(Token::Dot),
-				'-' => self.tokens.push(Token::Minus),
-				'+' => self.tokens.push(Token::Plus),
-				';' => self.tokens.push(Token::Semicolon),
-				'*' => self.tokens.push(Token::Star),
-				_ => {
-					if c == '"' {
-						read_start_offset = current_offset;
+		Some(result)
+	}
 
-						reading_string = true;
-						continue;
-					}
-					if c.is_numeric() {
-						read_start_offset = current_offset;
+	fn scan_token(&mut self) -> Option<Result<Spanned<Token<'src>>, LexError>> {
+		if let Some(result) = self.pending.pop_front() {
+			return Some(result);
+		}
 
-						reading_number = true;
-						string_buf.push(c);
-						continue;
+		if self.finished {
+			return None;
+		}
+
+		if self.offset == 0 {
+			// Every span is stored as a pair of u32 offsets, so refuse anything that can't be addressed that way
+			if let Err(e) = check_source_len(self.source.len()) {
+				// Still end in `Eof` like every other token stream, see `LexOutput`
+				self.pending.push_back(Ok(Spanned::new(Token::Eof, 0, 0)));
+				self.finished = true;
+				return Some(Err(e));
+			}
+		}
+
+		loop {
+			let start = self.offset;
+			self.token_start = self.here();
+			let Some(c) = self.advance() else {
+				self.finished = true;
+				return Some(Ok(Spanned::new(Token::Eof, start, start)));
+			};
+
+			let token = match c {
+				'(' => Token::LeftParen,
+				')' => Token::RightParen,
+				'{' => Token::LeftBrace,
+				'}' => Token::RightBrace,
+				'[' => Token::LeftBracket,
+				']' => Token::RightBracket,
+				',' => Token::Comma,
+				'.' => Token::Dot,
+				'-' => Token::Minus,
+				'+' => Token::Plus,
+				';' => Token::Semicolon,
+				'*' => Token::Star,
+				':' => {
+					if self.advance_if('=') {
+						Token::ColonEqual
+					} else {
+						Token::Colon
+					}
+				},
+				'/' => {
+					if self.advance_if('/') {
+						self.advance_while(|c| c != '\n');
+					} else if self.advance_if('*') {
+						if let Err(e) = self.skip_block_comment(start) {
+							return Some(Err(e));
+						}
+					} else {
+						return Some(Ok(Spanned::new(Token::Slash, start, self.offset)));
 					}
-					if c.is_alphabetic() {
-						read_start_offset = current_offset;
 
-						reading_identifier = true;
-						string_buf.push(c);
+					if !self.options.trivia {
 						continue;
 					}
+					Token::Comment(Cow::Borrowed(&self.source[start..self.offset]))
+				},
+				'"' => return Some(self.read_string(start)),
+				'“' | '”' if self.options.smart_quotes => return Some(self.read_string(start)),
+				_ => {
+					if c.is_whitespace() {
+						if !self.options.trivia {
+							continue;
+						}
+						self.advance_while(char::is_whitespace);
+						return Some(Ok(Spanned::new(Token::Whitespace(Cow::Borrowed(&self.source[start..self.offset])), start, self.offset)));
+					}
+					if c.is_ascii_digit() {
+						return Some(self.read_number(start));
+					}
+					if self.is_identifier_start(c) {
+						return Some(Ok(self.read_identifier(start)));
+					}
 					if OPERATORS.contains(c) {
-						if self.offset<bytes.len() && bytes[self.offset] as char == '=' {
-							self.offset += 1;
+						// A doubled `<`/`>` is a shift, so `a << b` never reaches the `<=` check below
+						if (c == '<' || c == '>') && self.advance_if(c) {
+							if c == '<' {
+								Token::LessLess
+							} else {
+								Token::GreaterGreater
+							}
+						} else if self.advance_if('=') {
 							match c {
-								'!' => self.tokens.push(Token::BangEqual),
-								'=' => self.tokens.push(Token::EqualEqual),
-								'<' => self.tokens.push(Token::LessEqual),
-								'>' => self.tokens.push(Token::GreatEqual),
+								'!' => Token::BangEqual,
+								'=' => Token::EqualEqual,
+								'<' => Token::LessEqual,
+								'>' => Token::GreatEqual,
 								_ => unreachable!()
 							}
 						} else {
 							match c {
-								'!' => self.tokens.push(Token::Bang),
-								'=' => self.tokens.push(Token::Equal),
-								'<' => self.tokens.push(Token::Less),
-								'>' => self.tokens.push(Token::Greater),
+								'!' => Token::Bang,
+								'=' => Token::Equal,
+								'<' => Token::Less,
+								'>' => Token::Greater,
 								_ => unreachable!()
 							}
 						}
-						continue;
+					} else {
+						return Some(Err(LexError::UnexpectedCharacter {
+							ch: c,
+							location: self.location(start)
+						}));
 					}
-					if c == '/' {
-						if self.offset<bytes.len() && bytes[self.offset] as char == '/' {
-							while self.offset < bytes.len() && bytes[self.offset] as char != '\n' {
-								self.offset += 1;
-							}
-						} else {
-							self.tokens.push(Token::Slash);
-						}
-						continue;
+				}
+			};
+
+			return Some(Ok(Spanned::new(token, start, self.offset)));
+		}
+	}
+
+	/// Skips the rest of a `/* ... */` comment whose opening `/*` is at `start`.  Block comments nest.
+	fn skip_block_comment(&mut self, start: usize) -> Result<(), LexError> {
+		let mut depth: usize = 1;
+
+		while depth > 0 {
+			match self.advance() {
+				Some('/') if self.advance_if('*') => depth += 1,
+				Some('*') if self.advance_if('/') => depth -= 1,
+				Some(_) => {},
+				None => return Err(LexError::UnterminatedBlockComment {
+					location: self.location(start)
+				})
+			}
+		}
+
+		Ok(())
+	}
+
+	fn read_string(&mut self, start: usize) -> Result<Spanned<Token<'src>>, LexError> {
+		let content_start = self.offset;
+		let content_end;
+		// Only allocated once an escape means the value no longer matches the source text
+		let mut rebuilt: Option<String> = None;
+
+		loop {
+			// Stop short of the newline so the next line is scanned as usual
+			if self.options.single_line_strings && self.peek() == Some('\n') {
+				self.pending.push_back(Err(LexError::UnterminatedString {
+					location: self.location(start)
+				}));
+				return self.pending.pop_front().unwrap();
+			}
+
+			let escape_offset = self.offset;
+			let escape_location = self.here();
+			let Some(c) = self.advance() else {
+				self.pending.push_back(Err(LexError::UnterminatedString {
+					location: self.location(start)
+				}));
+				return self.pending.pop_front().unwrap();
+			};
+
+			if c == '"' || (self.options.smart_quotes && (c == '“' || c == '”')) {
+				content_end = escape_offset;
+				break;
+			}
+
+			if c != '\\' || self.options.raw_strings {
+				if let Some(string_buf) = &mut rebuilt {
+					string_buf.push(c);
+				}
+				continue;
+			}
+
+			let string_buf = rebuilt.get_or_insert_with(|| self.source[content_start..escape_offset].to_string());
+
+			match self.advance() {
+				Some(c @ ('x' | 'u')) => {
+					let (decoded, end) = decode_escape(self.source.as_bytes(), self.offset, c);
+					match decoded {
+						Ok(ch) => string_buf.push(ch),
+						Err(reason) => self.pending.push_back(Err(LexError::InvalidEscape {
+							text: self.source[escape_offset..end].to_string(),
+							reason,
+							location: escape_location
+						}))
 					}
-					errors.push(self.generate_report(format!("Invalid token '{}'", c), current_offset))
+					// Escape sequences are ASCII, so each byte is one column
+					self.column += end - self.offset;
+					self.offset = end;
+				},
+				Some('n') => string_buf.push('\n'),
+				Some('t') => string_buf.push('\t'),
+				Some('r') => string_buf.push('\r'),
+				Some('0') => string_buf.push('\0'),
+				Some(c @ ('"' | '\\')) => string_buf.push(c),
+				Some(_) => self.pending.push_back(Err(LexError::InvalidEscape {
+					text: self.source[escape_offset..self.offset].to_string(),
+					reason: EscapeError::Unknown,
+					location: escape_location
+				})),
+				// A backslash right before the end of input, reported as an unterminated string on the next pass
+				None => {}
+			}
+		}
+
+		let value = match rebuilt {
+			Some(string_buf) => Cow::Owned(string_buf),
+			None => Cow::Borrowed(&self.source[content_start..content_end])
+		};
+		let token = Spanned::new(Token::String(value), start, self.offset);
+
+		if self.pending.is_empty() {
+			return Ok(token);
+		}
+
+		// Hand out the escape errors first, in the order they were found, followed by the string itself
+		self.pending.push_back(Ok(token));
+		self.pending.pop_front().unwrap()
+	}
+
+	/// Numbers are digits, optionally followed by a `.` and more digits.  A `.` that isn't followed by a digit
+	/// is left alone to become a `Token::Dot`, so `123.` is `Number(123)`, `Dot`.  A second fractional part,
+	/// as in `1.2.3`, is an error.
+	///
+	/// `0x`, `0b` and `0o` prefixes introduce hexadecimal, binary and octal integers.
+	fn read_number(&mut self, start: usize) -> Result<Spanned<Token<'src>>, LexError> {
+		if self.source[start..].starts_with('0') {
+			let radix = match self.peek() {
+				Some('x') => Some(16),
+				Some('b') => Some(2),
+				Some('o') => Some(8),
+				_ => None
+			};
+			if let Some(radix) = radix {
+				self.advance();
+				return self.read_radix_integer(start, radix);
+			}
+		}
+
+		self.advance_while(|c| c.is_ascii_digit() || c == '_');
+
+		let separator = self.peek().is_some_and(|c| c == '.' || (self.options.decimal_comma && c == ','));
+		if separator && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+			self.advance();
+			self.advance_while(|c| c.is_ascii_digit() || c == '_');
+
+			if self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+				let location = self.here();
+				// Swallow the rest of the literal so it doesn't turn into more tokens
+				while self.peek() == Some('.') && self.peek_next().is_some_and(|c| c.is_ascii_digit()) {
+					self.advance();
+					self.advance_while(|c| c.is_ascii_digit() || c == '_');
 				}
+				return Err(LexError::UnexpectedDotInNumber {
+					location
+				});
 			}
 		}
 
-		if reading_string {
-			errors.push(
-				self.generate_report(
-					format!("Unterminated string {}",
-						std::mem::take(&mut string_buf)
-						.into_iter()
-						.collect::<String>()
-					),
-					read_start_offset
-				)
-			)
+		let num_string = &self.source[start..self.offset];
+
+		if !valid_digit_separators(num_string) {
+			return Err(LexError::InvalidNumber {
+				text: num_string.to_string(),
+				location: self.location(start)
+			});
+		}
+
+		// A literal too big for an `f64` parses as infinity, which has no lexeme that scans back to it
+		match num_string.replace('_', "").replace(',', ".").parse::<f64>() {
+			Ok(num) if num.is_finite() => Ok(Spanned::new(Token::Number(num), start, self.offset)),
+			_ => Err(LexError::InvalidNumber {
+				text: num_string.to_string(),
+				location: self.location(start)
+			})
 		}
+	}
 
-		self.tokens.push(Token::Eof);
+	fn read_radix_integer(&mut self, start: usize, radix: u32) -> Result<Spanned<Token<'src>>, LexError> {
+		let digits_start = self.offset;
+		// Take the whole alphanumeric run so a stray digit like the `2` in `0b102` is reported rather than split off
+		self.advance_while(|c| c.is_ascii_alphanumeric() || c == '_');
+		let digits = &self.source[digits_start..self.offset];
+
+		if digits.is_empty() || !valid_digit_separators(digits) || !digits.chars().all(|c| c == '_' || c.is_digit(radix)) {
+			return Err(LexError::InvalidNumber {
+				text: self.source[start..self.offset].to_string(),
+				location: self.location(start)
+			});
+		}
 
-		if errors.len() > 0 {
-			return Err(errors.join("\n"));
+		// Accumulate as a float so that literals too large for any integer type still get their nearest value
+		let num = digits.chars()
+			.filter(|&c| c != '_')
+			.fold(0.0, |num, c| num * radix as f64 + c.to_digit(radix).unwrap() as f64);
+		if !num.is_finite() {
+			return Err(LexError::InvalidNumber {
+				text: self.source[start..self.offset].to_string(),
+				location: self.location(start)
+			});
 		}
+		Ok(Spanned::new(Token::Number(num), start, self.offset))
+	}
+
+	fn is_identifier_start(&self, c: char) -> bool {
+		c == '_' || c.is_ascii_alphabetic() || (self.options.unicode_identifiers && c.is_alphabetic())
+	}
+
+	/// Identifiers follow the book: `[A-Za-z_][A-Za-z0-9_]*`, so `for_each` is a single identifier rather
+	/// than `for` followed by junk.  Other letters and digits are only allowed with `unicode_identifiers`.
+	fn read_identifier(&mut self, start: usize) -> Spanned<Token<'src>> {
+		let unicode = self.options.unicode_identifiers;
+		self.advance_while(|c| c == '_' || c.is_ascii_alphanumeric() || (unicode && c.is_alphanumeric()));
+
+		let source = self.source;
+		let token = match &source[start..self.offset] {
+			"and" => Token::And,
+			"assert" => Token::Assert,
+			"class" => Token::Class,
+			"do" => Token::Do,
+			"else" => Token::Else,
+			"false" => Token::False,
+			"fun" => Token::Fun,
+			"for" => Token::For,
+			"if" => Token::If,
+			"nil" => Token::Nil,
+			"or" => Token::Or,
+			"print" => Token::Print,
+			"return" => Token::Return,
+			"super" => Token::Super,
+			"this" => Token::This,
+			"true" => Token::True,
+			"var" => Token::Var,
+			"while" => Token::While,
+			iden => Token::Identifier(Cow::Borrowed(iden))
+		};
+
+		Spanned::new(token, start, self.offset)
+	}
+}
+
+impl<'src> Iterator for Tokenizer<'src> {
+	type Item = Result<Token<'src>, LexError>;
 
-		Ok(std::mem::take(&mut self.tokens))
+	fn next(&mut self) -> Option<Self::Item> {
+		self.next_token().map(|result| result.map(|token| token.value))
 	}
 }
 
@@ -260,7 +1175,7 @@ impl Tokenizer {
 mod tests {
 	use super::*;
 
-	fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+	fn tokenize(source: &str) -> Result<Vec<Token<'_>>, Vec<LexError>> {
 		let mut tokenizer = Tokenizer::new(source);
 		tokenizer.tokenize()
 	}
@@ -273,7 +1188,7 @@ mod tests {
 		assert_eq!(tokenize(source).unwrap(), vec![
 			Token::Print,
 			Token::String(
-				String::from("Hello, World!")
+				Cow::from("Hello, World!")
 			),
 			Token::Semicolon,
 			Token::Eof
@@ -290,7 +1205,7 @@ mod tests {
 		assert_eq!(tokenize(source).unwrap(), vec![
 			Token::Print,
 			Token::String(
-				String::from("Hello, World!")
+				Cow::from("Hello, World!")
 			),
 			Token::Semicolon,
 			Token::Eof
@@ -336,19 +1251,19 @@ mod tests {
 		assert_eq!(tokenize(source).unwrap(), vec![
 			Token::Print,
 			Token::String(
-				String::from("Hi")
+				Cow::from("Hi")
 			),
 			Token::Semicolon,
 
 			Token::Print,
 			Token::String(
-				String::from("\"Escapes\"")
+				Cow::from("\"Escapes\"")
 			),
 			Token::Semicolon,
 
 			Token::Print,
 			Token::String(
-				String::from("Self escapes \\")
+				Cow::from("Self escapes \\")
 			),
 			Token::Semicolon,
 
@@ -466,7 +1381,7 @@ mod tests {
 		assert_eq!(tokenize(source).unwrap(), vec![
 			Token::Var,
 			Token::Identifier(
-				String::from("a123")
+				Cow::from("a123")
 			),
 			Token::Equal,
 			Token::False,
@@ -474,7 +1389,7 @@ mod tests {
 
 			Token::Var,
 			Token::Identifier(
-				String::from("x")
+				Cow::from("x")
 			),
 			Token::Equal,
 			Token::Number(1.0),
@@ -482,7 +1397,7 @@ mod tests {
 
 			Token::Var,
 			Token::Identifier(
-				String::from("y")
+				Cow::from("y")
 			),
 			Token::Equal,
 			Token::Number(2.0),
@@ -490,21 +1405,1284 @@ mod tests {
 
 			Token::Print,
 			Token::Identifier(
-				String::from("a123")
+				Cow::from("a123")
 			),
 			Token::Semicolon,
 
 			Token::Print,
 			Token::Identifier(
-				String::from("x")
+				Cow::from("x")
 			),
 			Token::Plus,
 			Token::Identifier(
-				String::from("y")
+				Cow::from("y")
 			),
 			Token::Semicolon,
 
 			Token::Eof
 		])
 	}
+
+	#[test]
+	fn test_assert() {
+		let source = r#"
+		assert x == 1;
+		var assertion = true;
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Assert,
+			Token::Identifier(
+				Cow::from("x")
+			),
+			Token::EqualEqual,
+			Token::Number(1.0),
+			Token::Semicolon,
+
+			Token::Var,
+			Token::Identifier(
+				Cow::from("assertion")
+			),
+			Token::Equal,
+			Token::True,
+			Token::Semicolon,
+
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_spans() {
+		let mut tokenizer = Tokenizer::new("var ab = \"c\";  1.5 <=");
+		let spans: Vec<(u32, u32)> = tokenizer.tokenize_spanned().unwrap()
+			.into_iter()
+			.map(|token| (token.span.start, token.span.end))
+			.collect();
+		assert_eq!(spans, vec![
+			(0, 3),
+			(4, 6),
+			(7, 8),
+			(9, 12),
+			(12, 13),
+			(15, 18),
+			(19, 21),
+			(21, 21)
+		])
+	}
+
+	#[test]
+	#[cfg(target_pointer_width = "64")]
+	fn test_token_size() {
+		assert_eq!(std::mem::size_of::<Span>(), 8);
+		assert_eq!(std::mem::size_of::<Token>(), 32);
+		assert_eq!(std::mem::size_of::<Spanned<Token>>(), 40);
+	}
+
+	#[test]
+	#[cfg(target_pointer_width = "64")]
+	fn test_source_too_large() {
+		let max = u32::MAX as usize;
+		assert_eq!(check_source_len(max), Ok(max));
+		let error = check_source_len(max + 1).unwrap_err();
+		assert_eq!(error, LexError::SourceTooLarge { len: max + 1 });
+		assert_eq!(error.location(), None);
+		assert_eq!(error.to_string(), "Error: Source too large (4294967296 bytes, the maximum is 4294967295 bytes)");
+	}
+
+	#[test]
+	fn test_source_too_large_still_ends_in_eof() {
+		MAX_SOURCE_LEN.with(|max| max.set(4));
+		let output = Tokenizer::new("print 1;").tokenize_lossy();
+		let result = tokenize("print 1;");
+		MAX_SOURCE_LEN.with(|max| max.set(u32::MAX as usize));
+
+		assert_eq!(output, LexOutput {
+			tokens: vec![Spanned::new(Token::Eof, 0, 0)],
+			errors: vec![LexError::SourceTooLarge { len: 8 }]
+		});
+		assert_eq!(result, Err(vec![LexError::SourceTooLarge { len: 8 }]));
+	}
+
+	#[test]
+	fn test_unexpected_character() {
+		let errors = tokenize("print 1;\nprint @;").unwrap_err();
+		assert_eq!(errors, vec![
+			LexError::UnexpectedCharacter {
+				ch: '@',
+				location: Location {
+					offset: 15,
+					line: 2,
+					column: 7
+				}
+			}
+		]);
+		assert_eq!(errors[0].to_string(), "[2:7] Error: Invalid token '@'");
+	}
+
+	#[test]
+	fn test_unterminated_string() {
+		let errors = tokenize("print \"abc").unwrap_err();
+		assert_eq!(errors, vec![
+			LexError::UnterminatedString {
+				location: Location {
+					offset: 6,
+					line: 1,
+					column: 7
+				}
+			}
+		]);
+		assert_eq!(errors[0].to_string(), "[1:7] Error: Unterminated string");
+	}
+
+	#[test]
+	fn test_number_dots() {
+		assert_eq!(tokenize("1.").unwrap(), vec![
+			Token::Number(1.0),
+			Token::Dot,
+			Token::Eof
+		]);
+		assert_eq!(tokenize(".5").unwrap(), vec![
+			Token::Dot,
+			Token::Number(5.0),
+			Token::Eof
+		]);
+		// A trailing dot is left for a method call or property access rather than being part of the number
+		assert_eq!(tokenize("1.2.").unwrap(), vec![
+			Token::Number(1.2),
+			Token::Dot,
+			Token::Eof
+		]);
+		assert_eq!(tokenize("print 1.2.3.4;"), Err(vec![
+			LexError::UnexpectedDotInNumber {
+				location: Location {
+					offset: 9,
+					line: 1,
+					column: 10
+				}
+			}
+		]));
+		let output = Tokenizer::new("1.2.3 + 1").tokenize_lossy();
+		assert_eq!(output.errors[0].to_string(), "[1:4] Error: Unexpected '.' in number literal");
+		assert_eq!(output.tokens.iter().map(|token| &token.value).collect::<Vec<_>>(), vec![
+			&Token::Plus,
+			&Token::Number(1.0),
+			&Token::Eof
+		]);
+		assert_eq!(tokenize("123.sqrt()").unwrap(), vec![
+			Token::Number(123.0),
+			Token::Dot,
+			Token::Identifier(
+				Cow::from("sqrt")
+			),
+			Token::LeftParen,
+			Token::RightParen,
+			Token::Eof
+		]);
+		assert_eq!(tokenize("print 42").unwrap(), vec![
+			Token::Print,
+			Token::Number(42.0),
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_multiple_errors() {
+		let errors = tokenize("@ 1 #").unwrap_err();
+		assert_eq!(errors.len(), 2);
+		assert_eq!(errors[0].location().unwrap().offset, 0);
+		assert_eq!(errors[1].location().unwrap().offset, 4);
+	}
+
+	#[test]
+	fn test_code_point_escapes() {
+		let source = r#"
+		print "\x41\u{42}\u{e9}\u{1F600}";
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Print,
+			Token::String(
+				Cow::from("ABé😀")
+			),
+			Token::Semicolon,
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_invalid_code_point_escapes() {
+		let source = "print \"some text \\x4G and \\u{110000} and \\u12\";";
+		assert_eq!(tokenize(source).unwrap_err(), vec![
+			LexError::InvalidEscape {
+				text: String::from("\\x4"),
+				reason: EscapeError::InvalidHex,
+				location: Location {
+					offset: 17,
+					line: 1,
+					column: 18
+				}
+			},
+			LexError::InvalidEscape {
+				text: String::from("\\u{110000}"),
+				reason: EscapeError::OutOfRange,
+				location: Location {
+					offset: 26,
+					line: 1,
+					column: 27
+				}
+			},
+			LexError::InvalidEscape {
+				text: String::from("\\u"),
+				reason: EscapeError::MissingBrace,
+				location: Location {
+					offset: 41,
+					line: 1,
+					column: 42
+				}
+			}
+		])
+	}
+
+	#[test]
+	fn test_iterator() {
+		let mut tokenizer = Tokenizer::new("print x;");
+		assert_eq!(tokenizer.next(), Some(Ok(Token::Print)));
+		assert_eq!(tokenizer.next(), Some(Ok(Token::Identifier(Cow::from("x")))));
+		assert_eq!(tokenizer.next(), Some(Ok(Token::Semicolon)));
+		assert_eq!(tokenizer.next(), Some(Ok(Token::Eof)));
+		assert_eq!(tokenizer.next(), None);
+		assert_eq!(tokenizer.next(), None);
+	}
+
+	#[test]
+	fn test_iterator_errors_in_stream() {
+		let tokenizer = Tokenizer::new("1 @ 2 \"\\x\" 3");
+		let results: Vec<_> = tokenizer.collect();
+		assert_eq!(results, vec![
+			Ok(Token::Number(1.0)),
+			Err(LexError::UnexpectedCharacter {
+				ch: '@',
+				location: Location {
+					offset: 2,
+					line: 1,
+					column: 3
+				}
+			}),
+			Ok(Token::Number(2.0)),
+			Err(LexError::InvalidEscape {
+				text: String::from("\\x"),
+				reason: EscapeError::InvalidHex,
+				location: Location {
+					offset: 7,
+					line: 1,
+					column: 8
+				}
+			}),
+			Ok(Token::String(Cow::from(""))),
+			Ok(Token::Number(3.0)),
+			Ok(Token::Eof)
+		])
+	}
+
+	#[test]
+	fn test_iterator_is_lazy() {
+		let mut source = String::from("var x = 1;");
+		for _ in 0..10_000 {
+			source.push_str(" @");
+		}
+		let mut tokenizer = Tokenizer::new(&source);
+		let first: Vec<_> = tokenizer.by_ref().take(5).collect();
+		assert!(first.iter().all(|result| result.is_ok()));
+		assert_eq!(tokenizer.offset, 10);
+	}
+
+	#[test]
+	fn test_unicode() {
+		let source = r#"
+		print "héllo wörld 😀";
+		var π = 2.5;
+		print "日本語" + π; @
+		"#;
+		let options = TokenizerOptions {
+			unicode_identifiers: true,
+			..Default::default()
+		};
+		let mut tokenizer = Tokenizer::new_with_options(source, options);
+		let results: Vec<_> = tokenizer.by_ref().collect();
+		assert_eq!(results, vec![
+			Ok(Token::Print),
+			Ok(Token::String(
+				Cow::from("héllo wörld 😀")
+			)),
+			Ok(Token::Semicolon),
+
+			Ok(Token::Var),
+			Ok(Token::Identifier(
+				Cow::from("π")
+			)),
+			Ok(Token::Equal),
+			Ok(Token::Number(2.5)),
+			Ok(Token::Semicolon),
+
+			Ok(Token::Print),
+			Ok(Token::String(
+				Cow::from("日本語")
+			)),
+			Ok(Token::Plus),
+			Ok(Token::Identifier(
+				Cow::from("π")
+			)),
+			Ok(Token::Semicolon),
+			Err(LexError::UnexpectedCharacter {
+				ch: '@',
+				location: Location {
+					offset: 73,
+					line: 4,
+					column: 20
+				}
+			}),
+
+			Ok(Token::Eof)
+		])
+	}
+
+	#[test]
+	fn test_token_kind_discriminants() {
+		for (index, kind) in TokenKind::ALL.iter().enumerate() {
+			assert_eq!(*kind as u8 as usize, index);
+			assert_eq!(TokenKind::from_u8(index as u8), Some(*kind));
+		}
+		assert_eq!(TokenKind::from_u8(TokenKind::ALL.len() as u8), None);
+	}
+
+	#[test]
+	fn test_block_comments() {
+		let source = r#"
+		/* a comment */ print 1;
+		print /* inline */ 2;
+		/*
+		 * spanning
+		 * lines
+		 */
+		print 3 /**/;
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Print,
+			Token::Number(1.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(2.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(3.0),
+			Token::Semicolon,
+
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_nested_block_comments() {
+		let source = r#"
+		/* outer /* inner */ still commented */
+		print 1 / 2; /* /* /* deep */ */ */ print 3;
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Print,
+			Token::Number(1.0),
+			Token::Slash,
+			Token::Number(2.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(3.0),
+			Token::Semicolon,
+
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_unterminated_block_comment() {
+		let errors = tokenize("print 1;\n  /* outer /* inner */ print 2;").unwrap_err();
+		assert_eq!(errors, vec![
+			LexError::UnterminatedBlockComment {
+				location: Location {
+					offset: 11,
+					line: 2,
+					column: 3
+				}
+			}
+		]);
+		assert_eq!(errors[0].to_string(), "[2:3] Error: Unterminated block comment");
+	}
+
+	#[test]
+	fn test_unicode_comments() {
+		let source = r#"
+		// 😀 naïve comment ✓
+		print "ok"; /* ünïcödé 🎉 */ print 2;
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Print,
+			Token::String(
+				Cow::from("ok")
+			),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(2.0),
+			Token::Semicolon,
+
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_unicode_error_column() {
+		// Both 'é' (2 bytes) and '😀' (4 bytes) count as a single column
+		let errors = tokenize("\"é😀\" #").unwrap_err();
+		assert_eq!(errors, vec![
+			LexError::UnexpectedCharacter {
+				ch: '#',
+				location: Location {
+					offset: 9,
+					line: 1,
+					column: 6
+				}
+			}
+		]);
+
+		let errors = tokenize("print ¿;").unwrap_err();
+		assert_eq!(errors, vec![
+			LexError::UnexpectedCharacter {
+				ch: '¿',
+				location: Location {
+					offset: 6,
+					line: 1,
+					column: 7
+				}
+			}
+		]);
+	}
+
+	#[test]
+	fn test_smart_quotes() {
+		let source = "print “Hello, World!”;";
+
+		let options = TokenizerOptions {
+			smart_quotes: true,
+			..Default::default()
+		};
+		assert_eq!(Tokenizer::new_with_options(source, options).tokenize().unwrap(), vec![
+			Token::Print,
+			Token::String(
+				Cow::from("Hello, World!")
+			),
+			Token::Semicolon,
+			Token::Eof
+		]);
+
+		let errors = tokenize(source).unwrap_err();
+		assert_eq!(errors.len(), 2);
+		assert!(matches!(errors[0], LexError::UnexpectedCharacter { ch: '“', .. }));
+		assert!(matches!(errors[1], LexError::UnexpectedCharacter { ch: '”', .. }));
+
+		// Curly quotes are ordinary characters inside a normal string
+		assert_eq!(tokenize("\"“hi”\"").unwrap(), vec![
+			Token::String(
+				Cow::from("“hi”")
+			),
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_escape_sequences() {
+		let source = r#"
+		print "line1\nline2\ttab\rreturn\0nul";
+		"#;
+		let tokens = tokenize(source).unwrap();
+		assert_eq!(tokens, vec![
+			Token::Print,
+			Token::String(
+				Cow::from("line1\nline2\ttab\rreturn\0nul")
+			),
+			Token::Semicolon,
+			Token::Eof
+		]);
+
+		let Token::String(value) = &tokens[1] else {
+			unreachable!()
+		};
+		assert_eq!(value.as_bytes()[5], 0x0A);
+	}
+
+	#[test]
+	fn test_unknown_escape() {
+		let errors = tokenize("print \"ok\\qok\";").unwrap_err();
+		assert_eq!(errors, vec![
+			LexError::InvalidEscape {
+				text: String::from("\\q"),
+				reason: EscapeError::Unknown,
+				location: Location {
+					offset: 9,
+					line: 1,
+					column: 10
+				}
+			}
+		]);
+		assert_eq!(errors[0].to_string(), "[1:10] Error: Invalid escape sequence '\\q'");
+	}
+
+	#[test]
+	fn test_unicode_escapes() {
+		assert_eq!(tokenize(r#""\u{e9}t\u{E9}""#).unwrap(), vec![
+			Token::String(
+				Cow::from("été")
+			),
+			Token::Eof
+		]);
+		assert_eq!(tokenize(r#""\u{1F600}""#).unwrap(), vec![
+			Token::String(
+				Cow::from("😀")
+			),
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_malformed_unicode_escapes() {
+		fn escape_error(source: &str) -> (EscapeError, String, usize) {
+			match &tokenize(source).unwrap_err()[..] {
+				[LexError::InvalidEscape { text, reason, location }] => (*reason, text.clone(), location.column),
+				errors => panic!("Expected a single escape error, got {:?}", errors)
+			}
+		}
+
+		assert_eq!(escape_error(r#""ab \u{12G4} cd""#), (EscapeError::InvalidHex, String::from("\\u{12G4}"), 5));
+		assert_eq!(escape_error(r#""ab \u{} cd""#), (EscapeError::InvalidHex, String::from("\\u{}"), 5));
+		assert_eq!(escape_error(r#""ab \u{1F600 cd""#), (EscapeError::MissingBrace, String::from("\\u{1F600"), 5));
+		assert_eq!(escape_error(r#""ab \u1F600 cd""#), (EscapeError::MissingBrace, String::from("\\u"), 5));
+		assert_eq!(escape_error(r#""ab \u{110000} cd""#), (EscapeError::OutOfRange, String::from("\\u{110000}"), 5));
+		assert_eq!(escape_error(r#""ab \u{1000000} cd""#), (EscapeError::OutOfRange, String::from("\\u{1000000}"), 5));
+		assert_eq!(escape_error(r#""ab \u{D800} cd""#), (EscapeError::Surrogate, String::from("\\u{D800}"), 5));
+	}
+
+	#[test]
+	fn test_each_escape() {
+		let escapes = [
+			(r#""\n""#, "\n"),
+			(r#""\t""#, "\t"),
+			(r#""\r""#, "\r"),
+			(r#""\0""#, "\0"),
+			(r#""\"""#, "\""),
+			(r#""\\""#, "\\")
+		];
+		for (source, expected) in escapes {
+			assert_eq!(tokenize(source).unwrap(), vec![
+				Token::String(
+					Cow::from(expected)
+				),
+				Token::Eof
+			], "escape {}", source);
+		}
+	}
+
+	#[test]
+	fn test_escape_at_end_of_file() {
+		// An escaped backslash right before the closing quote at the very end is fine...
+		assert_eq!(tokenize(r#"print "\\""#).unwrap(), vec![
+			Token::Print,
+			Token::String(
+				Cow::from("\\")
+			),
+			Token::Eof
+		]);
+
+		// ...but a lone trailing backslash escapes nothing and leaves the string open
+		assert_eq!(tokenize(r#"print "abc\"#).unwrap_err(), vec![
+			LexError::UnterminatedString {
+				location: Location {
+					offset: 6,
+					line: 1,
+					column: 7
+				}
+			}
+		]);
+		assert_eq!(tokenize(r#"print "abc\""#).unwrap_err(), vec![
+			LexError::UnterminatedString {
+				location: Location {
+					offset: 6,
+					line: 1,
+					column: 7
+				}
+			}
+		]);
+	}
+
+	#[test]
+	fn test_tokenize_twice() {
+		let mut tokenizer = Tokenizer::new("var x = 1; print x");
+		let first = tokenizer.tokenize().unwrap();
+		assert_eq!(first.len(), 8);
+		assert_eq!(tokenizer.tokenize().unwrap(), first);
+
+		let mut tokenizer = Tokenizer::new("1 @ 2");
+		let first = tokenizer.tokenize().unwrap_err();
+		assert_eq!(tokenizer.tokenize().unwrap_err(), first);
+	}
+
+	#[test]
+	fn test_tokenize_after_iterating() {
+		let mut tokenizer = Tokenizer::new("print 1;");
+		assert_eq!(tokenizer.next(), Some(Ok(Token::Print)));
+		assert_eq!(tokenizer.tokenize().unwrap(), vec![
+			Token::Print,
+			Token::Number(1.0),
+			Token::Semicolon,
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_iterator_matches_tokenize() {
+		let source = r#"
+		var greeting = "Hello";
+		while (count <= 10) { print greeting + "!"; count = count * 2; }
+		/* done */ print greeting != nil and !false; // trailing
+		"#;
+
+		let mut tokenizer = Tokenizer::new(source);
+		let mut streamed = vec![];
+		loop {
+			match tokenizer.next() {
+				Some(Ok(token)) => streamed.push(token),
+				Some(Err(e)) => panic!("{e}"),
+				None => break
+			}
+		}
+		assert_eq!(streamed.last(), Some(&Token::Eof));
+		assert_eq!(streamed, Tokenizer::new(source).tokenize().unwrap());
+	}
+
+	#[test]
+	fn test_block_comment_contents() {
+		let source = r#"
+		/* not a line comment: // print 1;
+		   not a string: "unterminated
+		   nor this: ' */ print 2;
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Print,
+			Token::Number(2.0),
+			Token::Semicolon,
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_location_after_block_comment() {
+		let errors = tokenize("/* one\ntwo\nthree */ @").unwrap_err();
+		assert_eq!(errors, vec![
+			LexError::UnexpectedCharacter {
+				ch: '@',
+				location: Location {
+					offset: 20,
+					line: 3,
+					column: 10
+				}
+			}
+		]);
+	}
+
+	#[test]
+	fn test_do_while() {
+		let source = r#"
+		do { done = double; } while (false);
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Do,
+			Token::LeftBrace,
+			Token::Identifier(
+				Cow::from("done")
+			),
+			Token::Equal,
+			Token::Identifier(
+				Cow::from("double")
+			),
+			Token::Semicolon,
+			Token::RightBrace,
+			Token::While,
+			Token::LeftParen,
+			Token::False,
+			Token::RightParen,
+			Token::Semicolon,
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_tokenize_lossy() {
+		let source = r#"
+		var x = 1;
+		print x @ 2;
+		print "done";
+		"#;
+		let output = Tokenizer::new(source).tokenize_lossy();
+		let tokens: Vec<Token> = output.tokens.into_iter().map(|token| token.value).collect();
+		assert_eq!(tokens, vec![
+			Token::Var,
+			Token::Identifier(
+				Cow::from("x")
+			),
+			Token::Equal,
+			Token::Number(1.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Identifier(
+				Cow::from("x")
+			),
+			Token::Number(2.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::String(
+				Cow::from("done")
+			),
+			Token::Semicolon,
+
+			Token::Eof
+		]);
+		assert_eq!(output.errors, vec![
+			LexError::UnexpectedCharacter {
+				ch: '@',
+				location: Location {
+					offset: 24,
+					line: 3,
+					column: 11
+				}
+			}
+		]);
+
+		assert_eq!(tokenize(source).unwrap_err(), output.errors);
+	}
+
+	#[test]
+	fn test_source_not_mutated() {
+		// Numbers and identifiers running into the end of input are still flushed,
+		// without padding the source to get there
+		let mut tokenizer = Tokenizer::new("print x + 12");
+		assert_eq!(tokenizer.tokenize().unwrap(), vec![
+			Token::Print,
+			Token::Identifier(
+				Cow::from("x")
+			),
+			Token::Plus,
+			Token::Number(12.0),
+			Token::Eof
+		]);
+		assert_eq!(tokenizer.source, "print x + 12");
+		assert_eq!(tokenizer.tokenize_spanned().unwrap().last().unwrap().span, Span { start: 12, end: 12 });
+	}
+
+	#[test]
+	fn test_zero_copy() {
+		let tokens = tokenize(r#"var name = "plain"; print "esc\"aped";"#).unwrap();
+		assert!(matches!(&tokens[1], Token::Identifier(Cow::Borrowed("name"))));
+		assert!(matches!(&tokens[3], Token::String(Cow::Borrowed("plain"))));
+		assert!(matches!(&tokens[6], Token::String(Cow::Owned(value)) if value == "esc\"aped"));
+
+		let owned: Token<'static> = tokenize("name").unwrap().remove(0).into_owned();
+		assert_eq!(owned, Token::Identifier(Cow::from("name")));
+	}
+
+	#[test]
+	fn test_radix_literals() {
+		let source = r#"
+		print 0xFF + 0xff;
+		print 0b1010;
+		print 0o755;
+		print 0 + 0x0 + 007;
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Print,
+			Token::Number(255.0),
+			Token::Plus,
+			Token::Number(255.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(10.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(493.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(0.0),
+			Token::Plus,
+			Token::Number(0.0),
+			Token::Plus,
+			Token::Number(7.0),
+			Token::Semicolon,
+
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_malformed_radix_literals() {
+		for (source, text) in [("0x;", "0x"), ("0b", "0b"), ("0b2;", "0b2"), ("0o78", "0o78"), ("0xFG", "0xFG")] {
+			assert_eq!(tokenize(source).unwrap_err(), vec![
+				LexError::InvalidNumber {
+					text: String::from(text),
+					location: Location {
+						offset: 0,
+						line: 1,
+						column: 1
+					}
+				}
+			], "literal {}", source);
+		}
+	}
+
+	#[test]
+	fn test_overflowing_numbers() {
+		let decimal = "9".repeat(400);
+		let hex = format!("0x{}", "F".repeat(300));
+		for text in [decimal.as_str(), &format!("{}.5", decimal), hex.as_str()] {
+			assert_eq!(tokenize(&format!("print {};", text)).unwrap_err(), vec![
+				LexError::InvalidNumber {
+					text: text.to_string(),
+					location: Location {
+						offset: 6,
+						line: 1,
+						column: 7
+					}
+				}
+			]);
+		}
+		// The largest finite values are still fine
+		assert_eq!(tokenize(&format!("{}", f64::MAX)).unwrap(), vec![Token::Number(f64::MAX), Token::Eof]);
+		assert!(matches!(tokenize(&format!("0x{}", "F".repeat(255))).unwrap()[0], Token::Number(num) if num.is_finite()));
+	}
+
+	#[test]
+	fn test_shift_operators() {
+		let source = r#"
+		a << 2;
+		a >> 1;
+		a <= b;
+		a < b;
+		a >= b << c > d;
+		"#;
+		let a = || Token::Identifier(Cow::from("a"));
+		let b = || Token::Identifier(Cow::from("b"));
+		assert_eq!(tokenize(source).unwrap(), vec![
+			a(), Token::LessLess, Token::Number(2.0), Token::Semicolon,
+			a(), Token::GreaterGreater, Token::Number(1.0), Token::Semicolon,
+			a(), Token::LessEqual, b(), Token::Semicolon,
+			a(), Token::Less, b(), Token::Semicolon,
+			a(), Token::GreatEqual, b(), Token::LessLess, Token::Identifier(Cow::from("c")), Token::Greater, Token::Identifier(Cow::from("d")), Token::Semicolon,
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_digit_separators() {
+		let source = r#"
+		print 1_000_000;
+		print 1.234_567;
+		print 0xFF_FF + 0b1010_1010;
+		"#;
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Print,
+			Token::Number(1000000.0),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(1.234567),
+			Token::Semicolon,
+
+			Token::Print,
+			Token::Number(65535.0),
+			Token::Plus,
+			Token::Number(170.0),
+			Token::Semicolon,
+
+			Token::Eof
+		])
+	}
+
+	#[test]
+	fn test_malformed_digit_separators() {
+		for (source, text) in [("1__0;", "1__0"), ("1_;", "1_"), ("1_.0;", "1_.0"), ("0x_F", "0x_F")] {
+			let errors = tokenize(source).unwrap_err();
+			assert_eq!(errors[0], LexError::InvalidNumber {
+				text: String::from(text),
+				location: Location {
+					offset: 0,
+					line: 1,
+					column: 1
+				}
+			}, "literal {}", source);
+		}
+	}
+
+	#[test]
+	fn test_identifier_underscores() {
+		let source = r#"
+		_private my_var foo_2 _ _123 for_each_ if_
+		"#;
+		let identifiers: Vec<Token> = ["_private", "my_var", "foo_2", "_", "_123", "for_each_", "if_"]
+			.into_iter()
+			.map(|name| Token::Identifier(Cow::from(name)))
+			.chain([Token::Eof])
+			.collect();
+		assert_eq!(tokenize(source).unwrap(), identifiers);
+	}
+
+	#[test]
+	fn test_underscored_keyword_lookalikes() {
+		let identifiers: Vec<Token> = ["__init__", "_for", "class_", "_while_", "__"]
+			.into_iter()
+			.map(|name| Token::Identifier(Cow::from(name)))
+			.chain([Token::Eof])
+			.collect();
+		assert_eq!(tokenize("__init__ _for class_ _while_ __").unwrap(), identifiers);
+
+		assert_eq!(tokenize("for _for").unwrap(), vec![
+			Token::For,
+			Token::Identifier(Cow::from("_for")),
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_unicode_identifiers_option() {
+		let source = "var π = 1;";
+
+		let errors = tokenize(source).unwrap_err();
+		assert!(matches!(errors[..], [LexError::UnexpectedCharacter { ch: 'π', .. }]));
+
+		let options = TokenizerOptions {
+			unicode_identifiers: true,
+			..Default::default()
+		};
+		assert_eq!(Tokenizer::new_with_options(source, options).tokenize().unwrap(), vec![
+			Token::Var,
+			Token::Identifier(
+				Cow::from("π")
+			),
+			Token::Equal,
+			Token::Number(1.0),
+			Token::Semicolon,
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_max_errors() {
+		let source = "print 1; @ # $ print 2; ~";
+		let options = TokenizerOptions {
+			max_errors: Some(2),
+			..Default::default()
+		};
+		let mut tokenizer = Tokenizer::new_with_options(source, options);
+		let output = tokenizer.tokenize_lossy();
+
+		assert_eq!(output.errors.len(), 2);
+		assert_eq!(output.tokens.iter().map(|token| &token.value).collect::<Vec<_>>(), vec![
+			&Token::Print,
+			&Token::Number(1.0),
+			&Token::Semicolon,
+			&Token::Eof
+		]);
+		assert_eq!(tokenizer.bytes_consumed(), 12);
+		assert!(tokenizer.bytes_consumed() < source.len());
+		assert_eq!(output.tokens.last().unwrap().span, Span { start: 12, end: 12 });
+
+		let mut tokenizer = Tokenizer::new(source);
+		assert_eq!(tokenizer.tokenize_lossy().errors.len(), 4);
+		assert_eq!(tokenizer.bytes_consumed(), source.len());
+	}
+
+	#[test]
+	fn test_many_errors_are_fast() {
+		let source = "@".repeat(50_000) + "\n" + &"€".repeat(50_000);
+		let started = std::time::Instant::now();
+		let output = Tokenizer::new(&source).tokenize_lossy();
+		assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+		assert_eq!(output.errors.len(), 100_000);
+		// Checking every error against the slow path would itself be quadratic
+		for error in output.errors.iter().step_by(4_999) {
+			let location = error.location().unwrap();
+			assert_eq!((location.line, location.column), get_2d_location(&source, location.offset));
+		}
+	}
+
+	#[test]
+	fn test_colon_equal() {
+		let n = || Token::Identifier(Cow::from("n"));
+		assert_eq!(tokenize("while (n := next()) { print n; }"), Ok(vec![
+			Token::While, Token::LeftParen, n(), Token::ColonEqual, Token::Identifier(Cow::from("next")), Token::LeftParen, Token::RightParen, Token::RightParen,
+			Token::LeftBrace, Token::Print, n(), Token::Semicolon, Token::RightBrace,
+			Token::Eof
+		]));
+
+		assert_eq!(tokenize("n : = 1 :"), Ok(vec![
+			n(), Token::Colon, Token::Equal, Token::Number(1.0), Token::Colon,
+			Token::Eof
+		]));
+	}
+
+	#[test]
+	fn test_lexemes() {
+		assert_eq!(Token::LeftParen.lexeme(), "(");
+		assert_eq!(Token::BangEqual.lexeme(), "!=");
+		assert_eq!(Token::While.lexeme(), "while");
+		assert_eq!(Token::Eof.lexeme(), "");
+		assert_eq!(Token::Number(1.0).lexeme(), "1");
+		assert_eq!(Token::Number(1.5).lexeme(), "1.5");
+		assert_eq!(Token::Identifier(Cow::from("x")).lexeme(), "x");
+		assert_eq!(Token::String(Cow::from("say \"hi\"\\n")).lexeme(), r#""say \"hi\"\\n""#);
+		assert_eq!(Token::String(Cow::from("a\nb\u{7}")).lexeme(), r#""a\nb\u{7}""#);
+	}
+
+	#[test]
+	fn test_lexeme_round_trip() {
+		let corpus = [
+			"var a = (1 + 2.5) * 3 / 4 - 0x1F;",
+			"class Foo < Bar { init() { this.x = super.y; } }",
+			"fun f(a, b) { return a >= b and a != b or !(a <= b); }",
+			"while (n := next()) { print n << 2 >> 1; }",
+			"do { x = x - 1; } while (x > 0); assert x == 0;",
+			"if (nil) {} else { for (var i = 0; i < 10; i = i + 1) print false; }",
+			r#"print "quote \" backslash \\ newline \n tab \t nul \0 escapes \x41\u{1F600}";"#,
+			"print 123456789012345678901234567890 + 0.1 + 0b1010_1010;",
+			"print \"\";"
+		];
+
+		for source in corpus {
+			let tokens = tokenize(source).unwrap();
+			let text = tokens.iter().map(|token| token.lexeme()).collect::<Vec<_>>().join(" ");
+			assert_eq!(tokenize(&text).unwrap(), tokens, "{}", text);
+		}
+	}
+
+	#[test]
+	fn test_trivia() {
+		let source = "// header\nprint 1; /* a /* nested */ comment */\n\tprint  2;// trailing";
+		let options = TokenizerOptions {
+			trivia: true,
+			..Default::default()
+		};
+		let tokens = Tokenizer::new_with_options(source, options).tokenize_spanned().unwrap();
+
+		let whitespace = |text| Token::Whitespace(Cow::from(text));
+		assert_eq!(tokens.iter().map(|token| &token.value).collect::<Vec<_>>(), vec![
+			&Token::Comment(Cow::from("// header")),
+			&whitespace("\n"),
+			&Token::Print,
+			&whitespace(" "),
+			&Token::Number(1.0),
+			&Token::Semicolon,
+			&whitespace(" "),
+			&Token::Comment(Cow::from("/* a /* nested */ comment */")),
+			&whitespace("\n\t"),
+			&Token::Print,
+			&whitespace("  "),
+			&Token::Number(2.0),
+			&Token::Semicolon,
+			&Token::Comment(Cow::from("// trailing")),
+			&Token::Eof
+		]);
+
+		// The spans tile the source, and here every lexeme is already in its canonical form
+		let mut offset = 0;
+		for token in &tokens {
+			assert_eq!(token.span.start as usize, offset);
+			assert_eq!(&source[token.span.start as usize..token.span.end as usize], token.value.lexeme());
+			offset = token.span.end as usize;
+		}
+		assert_eq!(offset, source.len());
+		assert_eq!(tokens.iter().map(|token| token.value.lexeme()).collect::<String>(), source);
+
+		// Without the option nothing changes
+		assert_eq!(tokenize(source).unwrap(), vec![
+			Token::Print, Token::Number(1.0), Token::Semicolon,
+			Token::Print, Token::Number(2.0), Token::Semicolon,
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_decimal_comma() {
+		let options = TokenizerOptions {
+			decimal_comma: true,
+			..Default::default()
+		};
+		let f = || Token::Identifier(Cow::from("f"));
+
+		assert_eq!(Tokenizer::new_with_options("print 3,25 + 2.5;", options.clone()).tokenize().unwrap(), vec![
+			Token::Print, Token::Number(3.25), Token::Plus, Token::Number(2.5), Token::Semicolon,
+			Token::Eof
+		]);
+		assert_eq!(Tokenizer::new_with_options("f(1, 2)", options.clone()).tokenize().unwrap(), vec![
+			f(), Token::LeftParen, Token::Number(1.0), Token::Comma, Token::Number(2.0), Token::RightParen,
+			Token::Eof
+		]);
+		// The ambiguous case the option trades away
+		assert_eq!(Tokenizer::new_with_options("f(1,2)", options).tokenize().unwrap(), vec![
+			f(), Token::LeftParen, Token::Number(1.2), Token::RightParen,
+			Token::Eof
+		]);
+		assert_eq!(tokenize("f(1,2)").unwrap(), vec![
+			f(), Token::LeftParen, Token::Number(1.0), Token::Comma, Token::Number(2.0), Token::RightParen,
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_raw_strings_option() {
+		let source = r#"print "C:\new\" + "\q";"#;
+		assert!(tokenize(source).is_err());
+
+		let options = TokenizerOptions::new().raw_strings(true);
+		assert_eq!(Tokenizer::new_with_options(source, options).tokenize().unwrap(), vec![
+			Token::Print,
+			Token::String(Cow::from(r"C:\new\")),
+			Token::Plus,
+			Token::String(Cow::from(r"\q")),
+			Token::Semicolon,
+			Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_single_line_strings_option() {
+		let source = "print \"one\nprint 2;";
+		// Normally the string runs on to the end of the source, taking the second statement with it
+		let output = Tokenizer::new(source).tokenize_lossy();
+		assert_eq!(output.errors.len(), 1);
+		assert_eq!(output.tokens.iter().map(|token| &token.value).collect::<Vec<_>>(), vec![&Token::Print, &Token::Eof]);
+
+		let options = TokenizerOptions::new().single_line_strings(true);
+		let output = Tokenizer::new_with_options(source, options).tokenize_lossy();
+		assert_eq!(output.errors, vec![
+			LexError::UnterminatedString {
+				location: Location {
+					offset: 6,
+					line: 1,
+					column: 7
+				}
+			}
+		]);
+		// Scanning picks up again on the next line
+		assert_eq!(output.tokens.iter().map(|token| &token.value).collect::<Vec<_>>(), vec![
+			&Token::Print,
+			&Token::Print,
+			&Token::Number(2.0),
+			&Token::Semicolon,
+			&Token::Eof
+		]);
+	}
+
+	#[test]
+	fn test_options_builder() {
+		let options = TokenizerOptions::new()
+			.smart_quotes(true)
+			.unicode_identifiers(true)
+			.max_errors(Some(3))
+			.trivia(true)
+			.decimal_comma(true)
+			.raw_strings(true)
+			.single_line_strings(true);
+		assert!(options.smart_quotes && options.unicode_identifiers && options.trivia);
+		assert!(options.decimal_comma && options.raw_strings && options.single_line_strings);
+		assert_eq!(options.max_errors, Some(3));
+
+		let defaults = TokenizerOptions::new();
+		assert!(!defaults.smart_quotes && !defaults.unicode_identifiers && !defaults.trivia);
+		assert!(!defaults.decimal_comma && !defaults.raw_strings && !defaults.single_line_strings);
+		assert_eq!(defaults.max_errors, None);
+	}
+
+	#[test]
+	fn test_display() {
+		let rendered: Vec<String> = [
+			Token::LeftBrace,
+			Token::GreatEqual,
+			Token::ColonEqual,
+			Token::Fun,
+			Token::Identifier(Cow::from("foo")),
+			Token::String(Cow::from("hi \"there\"")),
+			Token::Number(3.0),
+			Token::Number(0.25),
+			Token::Comment(Cow::from("// note")),
+			Token::Eof
+		].iter().map(|token| token.to_string()).collect();
+
+		assert_eq!(rendered, ["{", ">=", ":=", "fun", "foo", r#""hi \"there\"""#, "3", "0.25", "// note", "<eof>"]);
+	}
+
+	#[test]
+	fn test_eof_leading_trivia() {
+		let options = TokenizerOptions::new().trivia(true);
+		let source = "print 1;\n// the end";
+		let output = Tokenizer::new_with_options(source, options.clone()).tokenize_lossy();
+		assert_eq!(output.eof_leading_trivia(), &[
+			Spanned {
+				value: Token::Whitespace(Cow::from("\n")),
+				span: Span { start: 8, end: 9 }
+			},
+			Spanned {
+				value: Token::Comment(Cow::from("// the end")),
+				span: Span { start: 9, end: 19 }
+			}
+		]);
+		assert_eq!(output.tokens.last().unwrap().span, Span { start: 19, end: 19 });
+
+		let output = Tokenizer::new_with_options("/* only a comment */\n", options.clone()).tokenize_lossy();
+		assert_eq!(output.eof_leading_trivia().iter().map(|token| &token.value).collect::<Vec<_>>(), vec![
+			&Token::Comment(Cow::from("/* only a comment */")),
+			&Token::Whitespace(Cow::from("\n"))
+		]);
+
+		assert!(Tokenizer::new_with_options("print 1;", options).tokenize_lossy().eof_leading_trivia().is_empty());
+		assert!(Tokenizer::new(source).tokenize_lossy().eof_leading_trivia().is_empty());
+	}
+
+	#[test]
+	fn test_clone() {
+		let tokens = tokenize("var greeting = \"hi\" + name; print 1.5;").unwrap();
+		let cloned = tokens.clone();
+		assert_eq!(cloned, tokens);
+
+		let spanned = Tokenizer::new("a \"\\n\"").tokenize_spanned().unwrap();
+		assert_eq!(spanned.clone(), spanned);
+
+		// Kinds stand in for tokens wherever a hashable key is needed
+		let mut counts: std::collections::HashMap<TokenKind, usize> = std::collections::HashMap::new();
+		for token in &cloned {
+			*counts.entry(token.kind()).or_default() += 1;
+		}
+		assert_eq!(counts[&TokenKind::Semicolon], 2);
+		assert_eq!(counts[&TokenKind::Identifier], 2);
+	}
+
+	#[test]
+	fn test_brackets() {
+		assert_eq!(tokenize("var a = [1, 2]; print a[0];").unwrap(), vec![
+			Token::Var, Token::Identifier(Cow::from("a")), Token::Equal,
+			Token::LeftBracket, Token::Number(1.0), Token::Comma, Token::Number(2.0), Token::RightBracket, Token::Semicolon,
+			Token::Print, Token::Identifier(Cow::from("a")), Token::LeftBracket, Token::Number(0.0), Token::RightBracket, Token::Semicolon,
+			Token::Eof
+		]);
+		assert_eq!(tokenize("][").unwrap(), vec![Token::RightBracket, Token::LeftBracket, Token::Eof]);
+	}
 }
\ No newline at end of file